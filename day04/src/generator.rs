@@ -0,0 +1,378 @@
+//! Random passport records in the puzzle's input format, for property tests
+//! and for producing large benchmark inputs.
+
+/// Every passport field, in the order they are generated before shuffling.
+pub const FIELDS: [&str; 8] = ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid", "cid"];
+
+/// The only field which may be left out of a valid passport.
+pub const OPTIONAL_FIELD: &str = "cid";
+
+/// Written in place of the fields when every one is left out, so that the
+/// passport still has a record of its own in the input. It isn't a passport
+/// field, so the validator ignores it.
+pub const FILLER_FIELD: &str = "xxx:0";
+
+const EYE_COLORS: [&str; 7] = ["amb", "blu", "brn", "gry", "grn", "hzl", "oth"];
+
+/// A way of damaging a field's value.
+///
+/// The validator only checks which fields are present, so a corrupted value
+/// does not change whether a passport is expected to be valid.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Corruption {
+    /// Use a number outside the field's allowed range (e.g. `byr:1850`)
+    OutOfRange,
+
+    /// Drop the unit or prefix from the value (e.g. `hgt:183`, `hcl:fffffd`)
+    BadFormat,
+
+    /// Replace the value with random letters and digits
+    Garbage,
+}
+
+impl Corruption {
+    pub const ALL: [Corruption; 3] = [
+        Corruption::OutOfRange,
+        Corruption::BadFormat,
+        Corruption::Garbage,
+    ];
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratorConfig {
+    pub seed: u64,
+
+    /// Chance that each required field is left out of a passport
+    pub omission_rate: f64,
+
+    /// Chance that the optional `cid` field is left out of a passport
+    pub cid_omission_rate: f64,
+
+    /// Chance that each present field has its value corrupted
+    pub corruption_rate: f64,
+
+    /// The kinds of corruption to pick from; empty disables corruption
+    pub corruptions: Vec<Corruption>,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            omission_rate: 0.05,
+            cid_omission_rate: 0.5,
+            corruption_rate: 0.0,
+            corruptions: Corruption::ALL.to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratedPassport {
+    /// The passport in the puzzle's format, without a trailing newline
+    pub text: String,
+
+    /// Whether the passport should be accepted by `PassportData::is_valid`
+    pub expected_valid: bool,
+
+    /// The fields which were left out
+    pub missing: Vec<&'static str>,
+
+    /// The fields whose values were corrupted, and how
+    pub corrupted: Vec<(&'static str, Corruption)>,
+}
+
+/// An endless, deterministic stream of random passports.
+#[derive(Clone, Debug)]
+pub struct PassportGenerator {
+    config: GeneratorConfig,
+    rng: Rng,
+}
+
+impl PassportGenerator {
+    pub fn new(config: GeneratorConfig) -> Self {
+        let rng = Rng::new(config.seed);
+        Self { config, rng }
+    }
+
+    pub fn next_passport(&mut self) -> GeneratedPassport {
+        let mut missing = vec![];
+        let mut corrupted = vec![];
+        let mut fields = vec![];
+
+        for &field in FIELDS.iter() {
+            let omission_rate = if field == OPTIONAL_FIELD {
+                self.config.cid_omission_rate
+            } else {
+                self.config.omission_rate
+            };
+
+            if self.rng.chance(omission_rate) {
+                missing.push(field);
+                continue;
+            }
+
+            let value = if !self.config.corruptions.is_empty()
+                && self.rng.chance(self.config.corruption_rate)
+            {
+                let idx = self.rng.below(self.config.corruptions.len() as u64) as usize;
+                let corruption = self.config.corruptions[idx];
+                corrupted.push((field, corruption));
+                self.corrupt_value(field, corruption)
+            } else {
+                self.valid_value(field)
+            };
+
+            fields.push(format!("{}:{}", field, value));
+        }
+
+        if fields.is_empty() {
+            fields.push(FILLER_FIELD.to_string());
+        }
+
+        self.rng.shuffle(&mut fields);
+
+        // Spread the fields over several lines, like the puzzle input does
+        let mut text = String::new();
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                text.push(if self.rng.chance(0.25) { '\n' } else { ' ' });
+            }
+            text.push_str(field);
+        }
+
+        let expected_valid = missing.iter().all(|&f| f == OPTIONAL_FIELD);
+
+        GeneratedPassport {
+            text,
+            expected_valid,
+            missing,
+            corrupted,
+        }
+    }
+
+    /// Generate `count` passports, returning them along with the full puzzle input.
+    pub fn generate_input(&mut self, count: usize) -> (String, Vec<GeneratedPassport>) {
+        let passports: Vec<_> = self.take(count).collect();
+        (to_input(&passports), passports)
+    }
+
+    fn valid_value(&mut self, field: &str) -> String {
+        match field {
+            "byr" => self.rng.range(1920, 2002).to_string(),
+            "iyr" => self.rng.range(2010, 2020).to_string(),
+            "eyr" => self.rng.range(2020, 2030).to_string(),
+            "hgt" => {
+                if self.rng.chance(0.5) {
+                    format!("{}cm", self.rng.range(150, 193))
+                } else {
+                    format!("{}in", self.rng.range(59, 76))
+                }
+            }
+            "hcl" => format!("#{}", self.rng.string(6, b"0123456789abcdef")),
+            "ecl" => {
+                let idx = self.rng.below(EYE_COLORS.len() as u64) as usize;
+                EYE_COLORS[idx].to_string()
+            }
+            "pid" => self.rng.string(9, b"0123456789"),
+            "cid" => self.rng.range(1, 999).to_string(),
+            x => panic!("unknown field {}", x),
+        }
+    }
+
+    fn corrupt_value(&mut self, field: &str, corruption: Corruption) -> String {
+        match corruption {
+            Corruption::OutOfRange => match field {
+                "byr" => self.rng.range(1800, 1919).to_string(),
+                "iyr" => self.rng.range(1950, 2009).to_string(),
+                "eyr" => self.rng.range(2031, 2100).to_string(),
+                "hgt" => format!("{}cm", self.rng.range(194, 300)),
+                "pid" => self.rng.string(10, b"0123456789"),
+                "cid" => self.rng.range(1000, 9999).to_string(),
+                // Fields without a range get the wrong length instead
+                "hcl" => format!("#{}", self.rng.string(3, b"0123456789abcdef")),
+                "ecl" => "zzz".to_string(),
+                x => panic!("unknown field {}", x),
+            },
+
+            Corruption::BadFormat => {
+                let value = self.valid_value(field);
+                match field {
+                    "hgt" => value.trim_end_matches(char::is_alphabetic).to_string(),
+                    "hcl" => value.trim_start_matches('#').to_string(),
+                    _ => format!("#{}", value),
+                }
+            }
+
+            Corruption::Garbage => {
+                let len = self.rng.range(1, 12) as usize;
+                self.rng
+                    .string(len, b"abcdefghijklmnopqrstuvwxyz0123456789")
+            }
+        }
+    }
+}
+
+impl Iterator for PassportGenerator {
+    type Item = GeneratedPassport;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_passport())
+    }
+}
+
+/// Join passports into a puzzle input, separated by blank lines.
+pub fn to_input(passports: &[GeneratedPassport]) -> String {
+    let mut input = String::new();
+
+    for passport in passports {
+        input.push_str(&passport.text);
+        input.push_str("\n\n");
+    }
+
+    input.pop();
+    input
+}
+
+/// Small splitmix64 generator; good enough for test data and fully reproducible.
+#[derive(Clone, Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// A number in `lo..=hi`
+    fn range(&mut self, lo: u32, hi: u32) -> u32 {
+        lo + self.below((hi - lo + 1) as u64) as u32
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        let x = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        x < p
+    }
+
+    fn string(&mut self, len: usize, alphabet: &[u8]) -> String {
+        (0..len)
+            .map(|_| alphabet[self.below(alphabet.len() as u64) as usize] as char)
+            .collect()
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{count_valid, PassportData};
+
+    fn config(seed: u64, omission_rate: f64, corruption_rate: f64) -> GeneratorConfig {
+        GeneratorConfig {
+            seed,
+            omission_rate,
+            corruption_rate,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_passports() {
+        let a: Vec<_> = PassportGenerator::new(config(7, 0.1, 0.1))
+            .take(20)
+            .collect();
+        let b: Vec<_> = PassportGenerator::new(config(7, 0.1, 0.1))
+            .take(20)
+            .collect();
+
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_validator_agrees_with_generator() {
+        for seed in 0..50 {
+            for passport in PassportGenerator::new(config(seed, 0.1, 0.3)).take(50) {
+                let mut data = PassportData::new();
+                for line in passport.text.lines() {
+                    data.update_from_str(line);
+                }
+
+                assert_eq!(data.is_valid(), passport.expected_valid, "{:?}", passport);
+            }
+        }
+    }
+
+    #[test]
+    fn test_count_valid_on_generated_input() {
+        for seed in 0..20 {
+            let mut gen = PassportGenerator::new(config(seed, 0.2, 0.2));
+            let (input, passports) = gen.generate_input(100);
+
+            let expected = passports.iter().filter(|p| p.expected_valid).count();
+            assert_eq!(count_valid(&input), expected);
+        }
+    }
+
+    #[test]
+    fn test_no_omissions_all_valid() {
+        let mut gen = PassportGenerator::new(config(3, 0.0, 1.0));
+        let (input, passports) = gen.generate_input(100);
+
+        assert!(passports.iter().all(|p| p.expected_valid));
+        assert_eq!(count_valid(&input), 100);
+    }
+
+    #[test]
+    fn test_full_omission_none_valid() {
+        let mut gen = PassportGenerator::new(config(3, 1.0, 0.0));
+        let (input, _) = gen.generate_input(100);
+
+        assert_eq!(count_valid(&input), 0);
+    }
+
+    #[test]
+    fn test_every_field_omitted() {
+        let config = GeneratorConfig {
+            cid_omission_rate: 1.0,
+            ..config(5, 1.0, 0.0)
+        };
+        let (input, passports) = PassportGenerator::new(config).generate_input(100);
+
+        assert!(passports.iter().all(|p| p.text == FILLER_FIELD));
+        assert_eq!(input.split("\n\n").count(), 100);
+        assert_eq!(count_valid(&input), 0);
+    }
+
+    #[test]
+    fn test_corruption_recorded() {
+        let config = GeneratorConfig {
+            corruptions: vec![Corruption::Garbage],
+            ..config(11, 0.0, 1.0)
+        };
+        let passport = PassportGenerator::new(config).next_passport();
+
+        assert!(!passport.corrupted.is_empty());
+        assert!(passport
+            .corrupted
+            .iter()
+            .all(|&(_, c)| c == Corruption::Garbage));
+    }
+}
@@ -0,0 +1,386 @@
+pub mod seat_map;
+
+use std::error::Error;
+use std::fmt;
+
+/// Describes how a boarding pass encodes a seat: the number of row and column
+/// letters, and which letter selects the lower and upper half on each axis.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    row_bits: u32,
+    col_bits: u32,
+    row_letters: (char, char),
+    col_letters: (char, char),
+}
+
+impl Layout {
+    /// The puzzle's aircraft: 128 rows (`F`/`B`) and 8 columns (`L`/`R`).
+    pub const STANDARD: Layout = Layout {
+        row_bits: 7,
+        col_bits: 3,
+        row_letters: ('F', 'B'),
+        col_letters: ('L', 'R'),
+    };
+
+    /// Create a layout, where each letter pair is `(lower, upper)`.
+    ///
+    /// Returns `None` if the seat IDs wouldn't fit in a `u32`, or if the same
+    /// letter is used for both halves of an axis.
+    pub fn new(
+        row_bits: u32,
+        col_bits: u32,
+        row_letters: (char, char),
+        col_letters: (char, char),
+    ) -> Option<Self> {
+        if row_bits.checked_add(col_bits).is_none_or(|bits| bits > 32) {
+            return None;
+        }
+
+        if row_letters.0 == row_letters.1 || col_letters.0 == col_letters.1 {
+            return None;
+        }
+
+        Some(Self {
+            row_bits,
+            col_bits,
+            row_letters,
+            col_letters,
+        })
+    }
+
+    pub fn row_bits(&self) -> u32 {
+        self.row_bits
+    }
+
+    pub fn col_bits(&self) -> u32 {
+        self.col_bits
+    }
+
+    pub fn row_letters(&self) -> (char, char) {
+        self.row_letters
+    }
+
+    pub fn col_letters(&self) -> (char, char) {
+        self.col_letters
+    }
+
+    /// Number of rows in the aircraft
+    pub fn rows(&self) -> u64 {
+        1 << self.row_bits
+    }
+
+    /// Number of seats in each row
+    pub fn cols(&self) -> u64 {
+        1 << self.col_bits
+    }
+
+    /// Number of letters in a boarding pass
+    pub fn pass_len(&self) -> usize {
+        (self.row_bits + self.col_bits) as usize
+    }
+
+    /// The seat with the given ID.
+    pub fn seat(&self, id: u32) -> Seat {
+        let col = (id as u64 & (self.cols() - 1)) as u32;
+        let row = (id as u64 >> self.col_bits) as u32;
+        Seat { row, col, id }
+    }
+
+    /// Decode a boarding pass, like `FBFBBFFRLR`, into its seat.
+    pub fn decode(&self, pass: &str) -> Result<Seat, DecodeError> {
+        let found = pass.chars().count();
+        if found != self.pass_len() {
+            return Err(DecodeError::WrongLength {
+                expected: self.pass_len(),
+                found,
+            });
+        }
+
+        let mut row = 0;
+        let mut col = 0;
+
+        for (position, letter) in pass.chars().enumerate() {
+            let (letters, value) = if position < self.row_bits as usize {
+                (self.row_letters, &mut row)
+            } else {
+                (self.col_letters, &mut col)
+            };
+
+            let next_bit = if letter == letters.0 {
+                0
+            } else if letter == letters.1 {
+                1
+            } else {
+                return Err(DecodeError::InvalidLetter {
+                    position,
+                    letter,
+                    expected: letters,
+                });
+            };
+
+            *value = (*value << 1) | next_bit;
+        }
+
+        Ok(Seat::new(self, row, col))
+    }
+
+    /// Encode the seat at `row` and `col` as a boarding pass.
+    /// Returns `None` if the seat isn't part of this layout.
+    pub fn encode(&self, row: u32, col: u32) -> Option<String> {
+        if row as u64 >= self.rows() || col as u64 >= self.cols() {
+            return None;
+        }
+
+        let mut pass = String::with_capacity(self.pass_len());
+        push_bits(&mut pass, row, self.row_bits, self.row_letters);
+        push_bits(&mut pass, col, self.col_bits, self.col_letters);
+
+        Some(pass)
+    }
+
+    /// Encode the seat with the given ID as a boarding pass.
+    /// Returns `None` if the ID is too large for this layout.
+    pub fn encode_id(&self, id: u32) -> Option<String> {
+        if id as u64 >= self.rows() * self.cols() {
+            return None;
+        }
+
+        let seat = self.seat(id);
+        self.encode(seat.row, seat.col)
+    }
+}
+
+/// Write the lowest `bits` bits of `value` as letters, most significant first.
+fn push_bits(pass: &mut String, value: u32, bits: u32, letters: (char, char)) {
+    for i in (0..bits).rev() {
+        let letter = if value >> i & 1 == 0 {
+            letters.0
+        } else {
+            letters.1
+        };
+
+        pass.push(letter);
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Seat {
+    pub row: u32,
+    pub col: u32,
+    pub id: u32,
+}
+
+impl Seat {
+    /// Create the seat at `row` and `col`, computing its ID for `layout`.
+    pub fn new(layout: &Layout, row: u32, col: u32) -> Self {
+        let id = ((row as u64) << layout.col_bits) as u32 | col;
+        Self { row, col, id }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The pass has the wrong number of letters for the layout
+    WrongLength { expected: usize, found: usize },
+
+    /// A letter isn't one of the two allowed for its axis
+    InvalidLetter {
+        position: usize,
+        letter: char,
+        expected: (char, char),
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::WrongLength { expected, found } => write!(
+                f,
+                "boarding pass has {} letters, expected {}",
+                found, expected
+            ),
+
+            DecodeError::InvalidLetter {
+                position,
+                letter,
+                expected,
+            } => write!(
+                f,
+                "invalid letter {:?} at position {}, expected {:?} or {:?}",
+                letter, position, expected.0, expected.1
+            ),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Decode a boarding pass for the puzzle's aircraft.
+pub fn decode_boarding_pass(pass: &str) -> Result<Seat, DecodeError> {
+    Layout::STANDARD.decode(pass)
+}
+
+/// Decode every boarding pass in `input`, one per line, ignoring blank lines.
+pub fn decode_boarding_passes(input: &str) -> Result<Vec<Seat>, DecodeError> {
+    input
+        .lines()
+        .map(str::trim)
+        .filter(|pass| !pass.is_empty())
+        .map(decode_boarding_pass)
+        .collect()
+}
+
+/// Encode a seat ID as a boarding pass for the puzzle's aircraft.
+pub fn encode_boarding_pass(id: u32) -> Option<String> {
+    Layout::STANDARD.encode_id(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seat_id() {
+        let pass = "FBFBBFFRLR";
+        assert_eq!(decode_boarding_pass(pass).unwrap().id, 357);
+    }
+
+    #[test]
+    fn test_decode_examples() {
+        let examples = [
+            ("BFFFBBFRRR", 70, 7, 567),
+            ("FFFBBBFRRR", 14, 7, 119),
+            ("BBFFBBFRLL", 102, 4, 820),
+        ];
+
+        for &(pass, row, col, id) in examples.iter() {
+            assert_eq!(decode_boarding_pass(pass), Ok(Seat { row, col, id }));
+        }
+    }
+
+    #[test]
+    fn test_decode_custom_layout() {
+        let layout = Layout::new(3, 2, ('a', 'b'), ('x', 'y')).unwrap();
+
+        assert_eq!(
+            layout.decode("babyx"),
+            Ok(Seat {
+                row: 5,
+                col: 2,
+                id: 22
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_boarding_passes() {
+        let seats = decode_boarding_passes("BFFFBBFRRR\nFFFBBBFRRR\n\nBBFFBBFRLL\n").unwrap();
+        let ids: Vec<_> = seats.iter().map(|s| s.id).collect();
+
+        assert_eq!(ids, vec![567, 119, 820]);
+        assert_eq!(decode_boarding_passes(""), Ok(vec![]));
+        assert!(decode_boarding_passes("BFFFBBFRRR\nBFFFBBFRR").is_err());
+    }
+
+    #[test]
+    fn test_decode_wrong_length() {
+        assert_eq!(
+            decode_boarding_pass("FBFBBFFRL"),
+            Err(DecodeError::WrongLength {
+                expected: 10,
+                found: 9
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_invalid_letter() {
+        assert_eq!(
+            decode_boarding_pass("FBFBBFFRXR"),
+            Err(DecodeError::InvalidLetter {
+                position: 8,
+                letter: 'X',
+                expected: ('L', 'R')
+            })
+        );
+
+        // Column letters aren't accepted for rows
+        assert_eq!(
+            decode_boarding_pass("FBFLBFFRLR"),
+            Err(DecodeError::InvalidLetter {
+                position: 3,
+                letter: 'L',
+                expected: ('F', 'B')
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_layout() {
+        assert_eq!(Layout::new(30, 3, ('F', 'B'), ('L', 'R')), None);
+        assert_eq!(Layout::new(u32::MAX, 1, ('F', 'B'), ('L', 'R')), None);
+        assert_eq!(Layout::new(7, 3, ('F', 'F'), ('L', 'R')), None);
+    }
+
+    #[test]
+    fn test_encode_examples() {
+        let examples = [
+            ("FBFBBFFRLR", 357),
+            ("BFFFBBFRRR", 567),
+            ("FFFBBBFRRR", 119),
+            ("BBFFBBFRLL", 820),
+        ];
+
+        for &(pass, id) in examples.iter() {
+            assert_eq!(encode_boarding_pass(id).as_deref(), Some(pass));
+        }
+
+        assert_eq!(
+            Layout::STANDARD.encode(70, 7).as_deref(),
+            Some("BFFFBBFRRR")
+        );
+    }
+
+    #[test]
+    fn test_encode_out_of_range() {
+        assert_eq!(encode_boarding_pass(1024), None);
+        assert_eq!(Layout::STANDARD.encode(128, 0), None);
+        assert_eq!(Layout::STANDARD.encode(0, 8), None);
+    }
+
+    #[test]
+    fn test_round_trip_standard() {
+        let layout = Layout::STANDARD;
+
+        for id in 0..1024 {
+            let pass = encode_boarding_pass(id).unwrap();
+            let seat = decode_boarding_pass(&pass).unwrap();
+
+            assert_eq!(seat, layout.seat(id));
+            assert_eq!(layout.encode(seat.row, seat.col), Some(pass));
+        }
+    }
+
+    #[test]
+    fn test_round_trip_custom_layouts() {
+        let layouts = [
+            Layout::new(0, 4, ('F', 'B'), ('L', 'R')).unwrap(),
+            Layout::new(5, 0, ('F', 'B'), ('L', 'R')).unwrap(),
+            Layout::new(6, 4, ('a', 'b'), ('x', 'y')).unwrap(),
+            Layout::new(9, 3, ('0', '1'), ('0', '1')).unwrap(),
+        ];
+
+        for layout in layouts.iter() {
+            for id in 0..(layout.rows() * layout.cols()) as u32 {
+                let pass = layout.encode_id(id).unwrap();
+                assert_eq!(layout.decode(&pass), Ok(layout.seat(id)));
+            }
+        }
+    }
+}
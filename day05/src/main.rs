@@ -1,69 +1,35 @@
+use day05::seat_map::SeatMap;
+use day05::{decode_boarding_passes, Layout, Seat};
+
 const INPUT: &str = include_str!("../input.txt");
 
 fn main() {
-    part1();
-    part2();
+    let seats = decode_boarding_passes(INPUT).unwrap();
+
+    part1(&seats);
+    part2(&seats);
 }
 
-fn part1() {
+fn part1(seats: &[Seat]) {
     println!("*** PART 1 ***");
 
-    let max_id = INPUT.lines().map(decode_boarding_pass).max().unwrap();
+    let max_id = seats.iter().map(|seat| seat.id).max().unwrap();
 
     println!("{}", max_id);
 }
 
-fn part2() {
-    // If every number within our 11-bit range is present, except for one, then
-    // bitwise XORing each id together will give us the missing number.
-    // This works because XORing tells us whether an even or odd number of bits are set over a given column.
-
+fn part2(seats: &[Seat]) {
     println!("*** PART 2 ***");
 
-    let missing_pass = INPUT
-        .lines()
-        .map(decode_boarding_pass)
-        .fold(0, |acc, id| acc ^ id);
-
-    println!("{}", missing_pass);
-}
-
-fn decode_boarding_pass(pass: &str) -> u16 {
-    let mut seat_id = 0;
-
-    // Very hacky way of doing this. This is very bad.
-    for i in 0..7 {
-        let next_bit = match pass.chars().nth(i).unwrap() {
-            'F' => 0,
-            'B' => 1,
-            x => panic!("invalid letter {}", x),
-        };
+    let map = SeatMap::from_seats(Layout::STANDARD, seats.iter().copied());
 
-        seat_id <<= 1;
-        seat_id |= next_bit;
+    let duplicates = map.duplicates();
+    if !duplicates.is_empty() {
+        println!("duplicate passes for {:?}", duplicates);
     }
 
-    for i in 7..10 {
-        let next_bit = match pass.chars().nth(i).unwrap() {
-            'L' => 0,
-            'R' => 1,
-            x => panic!("invalid letter {}", x),
-        };
-
-        seat_id <<= 1;
-        seat_id |= next_bit;
-    }
-
-    seat_id
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_seat_id() {
-        let pass = "FBFBBFFRLR";
-        assert_eq!(decode_boarding_pass(pass), 357);
+    match map.candidate_seats().as_slice() {
+        [seat] => println!("{}", seat.id),
+        candidates => println!("no unique seat; candidates are {:?}", candidates),
     }
 }
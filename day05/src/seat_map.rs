@@ -0,0 +1,219 @@
+use std::fmt;
+
+use crate::{Layout, Seat};
+
+/// Why a seat has nobody in it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Vacancy {
+    /// Before the first occupied seat, so it probably doesn't exist on this aircraft
+    Front,
+
+    /// After the last occupied seat, so it probably doesn't exist on this aircraft
+    Back,
+
+    /// Between occupied seats, so it's a real seat with nobody in it
+    Gap,
+}
+
+/// Which seats of an aircraft are taken, built up from decoded boarding passes.
+///
+/// Every seat of the layout is stored, so this is only meant for aircraft-sized layouts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SeatMap {
+    layout: Layout,
+    passes: Vec<u32>,
+}
+
+impl SeatMap {
+    pub fn new(layout: Layout) -> Self {
+        let seats = (layout.rows() * layout.cols()) as usize;
+
+        Self {
+            layout,
+            passes: vec![0; seats],
+        }
+    }
+
+    pub fn from_seats<I: IntoIterator<Item = Seat>>(layout: Layout, seats: I) -> Self {
+        let mut map = Self::new(layout);
+
+        for seat in seats {
+            map.insert(seat);
+        }
+
+        map
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// Record a boarding pass for `seat`.
+    /// Returns `false` if the seat already had a pass, i.e. this one is a duplicate.
+    ///
+    /// Panics if the seat isn't part of the layout.
+    pub fn insert(&mut self, seat: Seat) -> bool {
+        let passes = &mut self.passes[seat.id as usize];
+        *passes += 1;
+        *passes == 1
+    }
+
+    pub fn is_occupied(&self, id: u32) -> bool {
+        self.passes.get(id as usize).is_some_and(|&n| n > 0)
+    }
+
+    /// The seats that have a boarding pass, in ID order
+    pub fn occupied(&self) -> Vec<Seat> {
+        self.seats_where(|n| n > 0)
+    }
+
+    /// The seats with more than one boarding pass, in ID order
+    pub fn duplicates(&self) -> Vec<Seat> {
+        self.seats_where(|n| n > 1)
+    }
+
+    /// Every seat without a boarding pass, with the reason it's empty
+    pub fn empty_seats(&self) -> Vec<(Seat, Vacancy)> {
+        let bounds = self.first_and_last_occupied();
+
+        self.seats_where(|n| n == 0)
+            .into_iter()
+            .map(|seat| (seat, vacancy(seat.id, bounds)))
+            .collect()
+    }
+
+    /// The empty seats between the first and last occupied seats
+    pub fn gaps(&self) -> Vec<Seat> {
+        self.empty_seats()
+            .into_iter()
+            .filter(|&(_, vacancy)| vacancy == Vacancy::Gap)
+            .map(|(seat, _)| seat)
+            .collect()
+    }
+
+    /// The empty seats whose neighbours on both sides (by ID) are occupied.
+    /// For a complete manifest, this is exactly one seat: yours.
+    pub fn candidate_seats(&self) -> Vec<Seat> {
+        self.gaps()
+            .into_iter()
+            .filter(|seat| self.is_occupied(seat.id - 1) && self.is_occupied(seat.id + 1))
+            .collect()
+    }
+
+    fn seats_where<F: Fn(u32) -> bool>(&self, predicate: F) -> Vec<Seat> {
+        self.passes
+            .iter()
+            .enumerate()
+            .filter(|&(_, &n)| predicate(n))
+            .map(|(id, _)| self.layout.seat(id as u32))
+            .collect()
+    }
+
+    fn first_and_last_occupied(&self) -> Option<(u32, u32)> {
+        let first = self.passes.iter().position(|&n| n > 0)?;
+        let last = self.passes.iter().rposition(|&n| n > 0)?;
+        Some((first as u32, last as u32))
+    }
+}
+
+fn vacancy(id: u32, bounds: Option<(u32, u32)>) -> Vacancy {
+    match bounds {
+        Some((first, _)) if id < first => Vacancy::Front,
+        Some((_, last)) if id > last => Vacancy::Back,
+        Some(_) => Vacancy::Gap,
+        // Nobody has boarded, so there's no evidence that any seat exists
+        None => Vacancy::Front,
+    }
+}
+
+/// Draws the cabin with one line per row:
+/// `#` is occupied, `*` has duplicate passes, `.` is a gap and `-` is a
+/// seat at the front or back that probably doesn't exist.
+impl fmt::Display for SeatMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bounds = self.first_and_last_occupied();
+
+        for (i, row) in self.passes.chunks(self.layout.cols() as usize).enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            for (j, &n) in row.iter().enumerate() {
+                let id = (i * row.len() + j) as u32;
+
+                let c = match n {
+                    0 if vacancy(id, bounds) == Vacancy::Gap => '.',
+                    0 => '-',
+                    1 => '#',
+                    _ => '*',
+                };
+
+                write!(f, "{}", c)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_map(ids: &[u32]) -> SeatMap {
+        let layout = Layout::new(2, 2, ('F', 'B'), ('L', 'R')).unwrap();
+        SeatMap::from_seats(layout, ids.iter().map(|&id| layout.seat(id)))
+    }
+
+    #[test]
+    fn test_candidate_seat() {
+        let map = small_map(&[2, 3, 4, 6, 7, 8, 9]);
+
+        assert_eq!(map.candidate_seats(), vec![map.layout().seat(5)]);
+    }
+
+    #[test]
+    fn test_front_and_back_are_not_gaps() {
+        let map = small_map(&[2, 3, 5, 12]);
+
+        let empty = map.empty_seats();
+        let vacancy = |id: u32| empty.iter().find(|(s, _)| s.id == id).unwrap().1;
+
+        assert_eq!(vacancy(0), Vacancy::Front);
+        assert_eq!(vacancy(1), Vacancy::Front);
+        assert_eq!(vacancy(4), Vacancy::Gap);
+        assert_eq!(vacancy(11), Vacancy::Gap);
+        assert_eq!(vacancy(13), Vacancy::Back);
+        assert_eq!(vacancy(15), Vacancy::Back);
+
+        // Seat 4 is the only gap with both neighbours taken
+        assert_eq!(map.gaps().len(), 7);
+        assert_eq!(map.candidate_seats(), vec![map.layout().seat(4)]);
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let mut map = small_map(&[1, 2]);
+        let seat = map.layout().seat(2);
+
+        assert!(map.duplicates().is_empty());
+        assert!(!map.insert(seat));
+        assert_eq!(map.duplicates(), vec![seat]);
+    }
+
+    #[test]
+    fn test_render() {
+        let map = small_map(&[2, 3, 5, 5, 6, 9]);
+
+        assert_eq!(map.to_string(), "--##\n.*#.\n.#--\n----");
+    }
+
+    #[test]
+    fn test_empty_map() {
+        let map = small_map(&[]);
+
+        assert!(map.gaps().is_empty());
+        assert!(map.candidate_seats().is_empty());
+        assert_eq!(map.empty_seats().len(), 16);
+    }
+}
@@ -0,0 +1,416 @@
+//! Statistics over a whole survey, beyond the puzzle's sum of counts.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::group::{Group, Quorum};
+use crate::ParseError;
+
+/// Every group's declarations from one survey.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Survey {
+    groups: Vec<Group>,
+}
+
+/// How often one question was answered across the survey.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct QuestionStats {
+    pub question: char,
+
+    /// People who answered the question
+    pub people: usize,
+
+    /// Groups in which anyone answered the question
+    pub groups_anyone: usize,
+
+    /// Groups in which everyone answered the question
+    pub groups_everyone: usize,
+}
+
+impl Survey {
+    pub fn new(groups: Vec<Group>) -> Self {
+        Self { groups }
+    }
+
+    /// Parse a whole puzzle input, with groups separated by blank lines.
+    /// Blank groups (e.g. from trailing newlines) are skipped, and error lines
+    /// are counted from the start of the input.
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let mut groups = vec![];
+        let mut first_line = 0;
+
+        for group in input.split("\n\n") {
+            if !group.trim().is_empty() {
+                let group = Group::parse(group).map_err(|e| ParseError {
+                    line: e.line + first_line,
+                    ..e
+                })?;
+                groups.push(group);
+            }
+
+            // The group's own newlines, and the two that ended it
+            first_line += group.matches('\n').count() + 2;
+        }
+
+        Ok(Self::new(groups))
+    }
+
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    /// Total number of people across all groups
+    pub fn people(&self) -> usize {
+        self.groups.iter().map(Group::len).sum()
+    }
+
+    /// Sum over all groups of the number of questions meeting `quorum`.
+    /// This is the puzzle answer for `Anyone` (part 1) and `Everyone` (part 2).
+    pub fn sum_counts(&self, quorum: Quorum) -> usize {
+        self.groups
+            .iter()
+            .map(|group| group.answered_by(quorum).len())
+            .sum()
+    }
+
+    /// Statistics for every question that anyone answered, in question order
+    pub fn question_stats(&self) -> Vec<QuestionStats> {
+        let mut stats: BTreeMap<char, QuestionStats> = BTreeMap::new();
+
+        for group in self.groups.iter() {
+            let everyone = group.answered_by(Quorum::Everyone);
+
+            for (question, people) in group.question_counts() {
+                let entry = stats.entry(question).or_insert(QuestionStats {
+                    question,
+                    people: 0,
+                    groups_anyone: 0,
+                    groups_everyone: 0,
+                });
+
+                entry.people += people;
+                entry.groups_anyone += 1;
+                if everyone.contains(question) {
+                    entry.groups_everyone += 1;
+                }
+            }
+        }
+
+        stats.into_values().collect()
+    }
+
+    /// The questions answered by the most people (more than one if tied)
+    pub fn most_common(&self) -> Vec<char> {
+        let stats = self.question_stats();
+        let max = stats.iter().map(|s| s.people).max();
+
+        stats
+            .iter()
+            .filter(|s| Some(s.people) == max)
+            .map(|s| s.question)
+            .collect()
+    }
+
+    /// The questions answered by the fewest people, ignoring questions nobody answered
+    pub fn least_common(&self) -> Vec<char> {
+        let stats = self.question_stats();
+        let min = stats.iter().map(|s| s.people).min();
+
+        stats
+            .iter()
+            .filter(|s| Some(s.people) == min)
+            .map(|s| s.question)
+            .collect()
+    }
+
+    /// Sets of group indices whose members answered exactly the same
+    /// questions between them. Groups with a unique answer set are left out.
+    pub fn identical_groups(&self) -> Vec<Vec<usize>> {
+        let mut by_answers: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+
+        for (i, group) in self.groups.iter().enumerate() {
+            let answers = group.answered_by(Quorum::Anyone).iter().collect();
+            by_answers.entry(answers).or_default().push(i);
+        }
+
+        let mut identical: Vec<_> = by_answers
+            .into_values()
+            .filter(|groups| groups.len() > 1)
+            .collect();
+
+        identical.sort();
+        identical
+    }
+
+    /// The correlation (phi coefficient) between two questions over every
+    /// person in the survey: 1 if people always answer both or neither,
+    /// -1 if they always answer exactly one.
+    ///
+    /// Returns `None` if either question was answered by everyone or by nobody.
+    pub fn correlation(&self, a: char, b: char) -> Option<f64> {
+        // n[x][y] is the number of people with (answered a, answered b) == (x, y)
+        let mut n = [[0.0f64; 2]; 2];
+
+        for person in self.groups.iter().flat_map(|g| g.members()) {
+            n[person.contains(a) as usize][person.contains(b) as usize] += 1.0;
+        }
+
+        let a_yes = n[1][0] + n[1][1];
+        let a_no = n[0][0] + n[0][1];
+        let b_yes = n[0][1] + n[1][1];
+        let b_no = n[0][0] + n[1][0];
+
+        let denominator = (a_yes * a_no * b_yes * b_no).sqrt();
+        if denominator == 0.0 {
+            return None;
+        }
+
+        Some((n[1][1] * n[0][0] - n[1][0] * n[0][1]) / denominator)
+    }
+
+    /// The correlation between every pair of answered questions where it is defined
+    pub fn correlations(&self) -> Vec<(char, char, f64)> {
+        let questions: Vec<_> = self.question_stats().iter().map(|s| s.question).collect();
+        let mut correlations = vec![];
+
+        for (i, &a) in questions.iter().enumerate() {
+            for &b in questions.iter().skip(i + 1) {
+                if let Some(phi) = self.correlation(a, b) {
+                    correlations.push((a, b, phi));
+                }
+            }
+        }
+
+        correlations
+    }
+
+    /// A plain-text report with one row per question
+    pub fn table(&self) -> String {
+        let mut out = String::new();
+        let people = self.people();
+
+        writeln!(out, "{} groups, {} people", self.groups.len(), people).unwrap();
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "question  people  % people  groups (anyone)  groups (everyone)"
+        )
+        .unwrap();
+
+        for s in self.question_stats() {
+            let percent = 100.0 * s.people as f64 / people as f64;
+
+            writeln!(
+                out,
+                "{:>8}  {:>6}  {:>8.1}  {:>15}  {:>17}",
+                s.question, s.people, percent, s.groups_anyone, s.groups_everyone
+            )
+            .unwrap();
+        }
+
+        writeln!(out).unwrap();
+        writeln!(out, "most common:  {}", join_chars(&self.most_common())).unwrap();
+        writeln!(out, "least common: {}", join_chars(&self.least_common())).unwrap();
+        writeln!(out, "identical groups: {:?}", self.identical_groups()).unwrap();
+
+        let mut correlations = self.correlations();
+        correlations.sort_by(|x, y| y.2.abs().partial_cmp(&x.2.abs()).unwrap());
+
+        writeln!(out, "strongest correlations:").unwrap();
+        for (a, b, phi) in correlations.iter().take(5) {
+            writeln!(out, "  {} {}  {:+.3}", a, b, phi).unwrap();
+        }
+
+        out
+    }
+
+    /// The same report as `table`, as a JSON object
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+
+        write!(
+            out,
+            "{{\"groups\":{},\"people\":{},\"questions\":[",
+            self.groups.len(),
+            self.people()
+        )
+        .unwrap();
+
+        for (i, s) in self.question_stats().iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+
+            write!(
+                out,
+                "{{\"question\":{},\"people\":{},\"groups_anyone\":{},\"groups_everyone\":{}}}",
+                json_char(s.question),
+                s.people,
+                s.groups_anyone,
+                s.groups_everyone
+            )
+            .unwrap();
+        }
+
+        let json_chars = |chars: Vec<char>| {
+            let chars: Vec<_> = chars.into_iter().map(json_char).collect();
+            chars.join(",")
+        };
+
+        write!(
+            out,
+            "],\"most_common\":[{}],\"least_common\":[{}],\"identical_groups\":{:?},\"correlations\":[",
+            json_chars(self.most_common()),
+            json_chars(self.least_common()),
+            self.identical_groups()
+        )
+        .unwrap();
+
+        for (i, (a, b, phi)) in self.correlations().into_iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+
+            write!(
+                out,
+                "{{\"a\":{},\"b\":{},\"phi\":{:.6}}}",
+                json_char(a),
+                json_char(b),
+                phi
+            )
+            .unwrap();
+        }
+
+        out.push_str("]}");
+        out
+    }
+}
+
+fn join_chars(chars: &[char]) -> String {
+    let chars: Vec<_> = chars.iter().map(char::to_string).collect();
+    chars.join(" ")
+}
+
+/// A question as a JSON string literal
+fn json_char(c: char) -> String {
+    match c {
+        '"' => "\"\\\"\"".to_string(),
+        '\\' => "\"\\\\\"".to_string(),
+        c if c.is_control() => format!("\"\\u{:04x}\"", c as u32),
+        c => format!("\"{}\"", c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "abc\n\na\nb\nc\n\nab\nac\n\na\na\na\na\n\nb\n";
+
+    #[test]
+    fn test_parse_and_sums() {
+        let survey = Survey::parse(EXAMPLE).unwrap();
+
+        assert_eq!(survey.groups().len(), 5);
+        assert_eq!(survey.people(), 11);
+        assert_eq!(survey.sum_counts(Quorum::Anyone), 11);
+        assert_eq!(survey.sum_counts(Quorum::Everyone), 6);
+    }
+
+    #[test]
+    fn test_parse_skips_blank_groups() {
+        let survey = Survey::parse("ab\n\nc\n\n\n").unwrap();
+
+        assert_eq!(survey.groups().len(), 2);
+        assert_eq!(survey.people(), 2);
+        assert_eq!(Survey::parse("").unwrap().groups().len(), 0);
+    }
+
+    #[test]
+    fn test_parse_error_line() {
+        let err = Survey::parse("abc\n\na\nb c\n").unwrap_err();
+
+        assert_eq!(err.line, 3);
+        assert_eq!(err.position, 1);
+    }
+
+    #[test]
+    fn test_parse_error_line_after_blank_lines() {
+        let err = Survey::parse("ab\n\n\n\nc d").unwrap_err();
+        assert_eq!(err.line, 4);
+
+        let err = Survey::parse("ab\n\n\n\n\nx\n\ny z").unwrap_err();
+        assert_eq!(err.line, 7);
+    }
+
+    #[test]
+    fn test_question_stats() {
+        let survey = Survey::parse(EXAMPLE).unwrap();
+
+        assert_eq!(
+            survey.question_stats(),
+            vec![
+                QuestionStats {
+                    question: 'a',
+                    people: 8,
+                    groups_anyone: 4,
+                    groups_everyone: 3
+                },
+                QuestionStats {
+                    question: 'b',
+                    people: 4,
+                    groups_anyone: 4,
+                    groups_everyone: 2
+                },
+                QuestionStats {
+                    question: 'c',
+                    people: 3,
+                    groups_anyone: 3,
+                    groups_everyone: 1
+                },
+            ]
+        );
+
+        assert_eq!(survey.most_common(), vec!['a']);
+        assert_eq!(survey.least_common(), vec!['c']);
+    }
+
+    #[test]
+    fn test_identical_groups() {
+        let survey = Survey::parse(EXAMPLE).unwrap();
+
+        assert_eq!(survey.identical_groups(), vec![vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn test_correlation() {
+        let survey = Survey::parse("ab\nab\nc\nc").unwrap();
+
+        assert_eq!(survey.correlation('a', 'b'), Some(1.0));
+        assert_eq!(survey.correlation('a', 'c'), Some(-1.0));
+        assert_eq!(survey.correlation('a', 'z'), None);
+    }
+
+    #[test]
+    fn test_json() {
+        let survey = Survey::parse("ab\na").unwrap();
+
+        assert_eq!(
+            survey.to_json(),
+            "{\"groups\":1,\"people\":2,\"questions\":[\
+             {\"question\":\"a\",\"people\":2,\"groups_anyone\":1,\"groups_everyone\":1},\
+             {\"question\":\"b\",\"people\":1,\"groups_anyone\":1,\"groups_everyone\":0}],\
+             \"most_common\":[\"a\"],\"least_common\":[\"b\"],\"identical_groups\":[],\
+             \"correlations\":[]}"
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        let survey = Survey::parse(EXAMPLE).unwrap();
+        let table = survey.table();
+
+        assert!(table.starts_with("5 groups, 11 people\n"));
+        assert!(table.contains("most common:  a\n"));
+    }
+}
@@ -0,0 +1,42 @@
+//! Print statistics for a customs declaration survey.
+//!
+//! Usage: `survey [--json] [FILE]`, reading the puzzle input if no file is given.
+
+use std::env;
+use std::fs;
+use std::process;
+
+use day06::analytics::Survey;
+
+const INPUT: &str = include_str!("../../input.txt");
+
+fn main() {
+    let mut json = false;
+    let mut path = None;
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ => path = Some(arg),
+        }
+    }
+
+    let input = match path {
+        Some(path) => fs::read_to_string(&path).unwrap_or_else(|e| {
+            eprintln!("couldn't read {}: {}", path, e);
+            process::exit(1);
+        }),
+        None => INPUT.to_string(),
+    };
+
+    let survey = Survey::parse(&input).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    if json {
+        println!("{}", survey.to_json());
+    } else {
+        print!("{}", survey.table());
+    }
+}
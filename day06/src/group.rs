@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+
+use crate::{parse_people, Declaration, ParseError};
+
+/// How many members of a group must answer a question for it to count.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Quorum {
+    /// At least one member (part 1)
+    Anyone,
+
+    /// Every member (part 2)
+    Everyone,
+
+    /// At least this many members
+    AtLeast(usize),
+
+    /// At least this percentage of members, from 0 to 100
+    Percent(f64),
+}
+
+/// The declarations of everyone in a travel group.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Group {
+    members: Vec<Declaration>,
+}
+
+impl Group {
+    pub fn new(members: Vec<Declaration>) -> Self {
+        Self { members }
+    }
+
+    /// Parse a group, one person per line.
+    pub fn parse(group: &str) -> Result<Self, ParseError> {
+        Ok(Self::new(parse_people(group)?))
+    }
+
+    pub fn members(&self) -> &[Declaration] {
+        &self.members
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// How many members answered each question.
+    /// Questions nobody answered are left out.
+    pub fn question_counts(&self) -> BTreeMap<char, usize> {
+        let mut counts = BTreeMap::new();
+
+        for member in self.members.iter() {
+            for question in member.iter() {
+                *counts.entry(question).or_insert(0) += 1;
+            }
+        }
+
+        counts
+    }
+
+    /// The questions answered by enough members to meet `quorum`.
+    /// A question nobody answered never counts, even for `AtLeast(0)` or `Percent(0.0)`.
+    pub fn answered_by(&self, quorum: Quorum) -> Declaration {
+        match quorum {
+            Quorum::Anyone => self
+                .members
+                .iter()
+                .fold(Declaration::new(), |acc, member| acc.union(member)),
+
+            Quorum::Everyone => {
+                let mut members = self.members.iter();
+
+                let first = match members.next() {
+                    Some(member) => member.clone(),
+                    None => return Declaration::new(),
+                };
+
+                members.fold(first, |acc, member| acc.intersection(member))
+            }
+
+            Quorum::AtLeast(k) => self.questions_where(|count| count >= k),
+
+            Quorum::Percent(p) => {
+                let n = self.len() as f64;
+                // Compare without dividing so e.g. 70% of 10 is exactly 7
+                self.questions_where(|count| count as f64 * 100.0 >= p * n)
+            }
+        }
+    }
+
+    fn questions_where<F: Fn(usize) -> bool>(&self, predicate: F) -> Declaration {
+        self.question_counts()
+            .into_iter()
+            .filter(|&(_, count)| predicate(count))
+            .map(|(question, _)| question)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answered(group: &Group, quorum: Quorum) -> String {
+        group.answered_by(quorum).iter().collect()
+    }
+
+    #[test]
+    fn test_question_counts() {
+        let group = Group::parse("abc\nab\na").unwrap();
+        let counts: Vec<_> = group.question_counts().into_iter().collect();
+
+        assert_eq!(counts, vec![('a', 3), ('b', 2), ('c', 1)]);
+    }
+
+    #[test]
+    fn test_anyone_and_everyone() {
+        let group = Group::parse("abc\nab\na").unwrap();
+
+        assert_eq!(answered(&group, Quorum::Anyone), "abc");
+        assert_eq!(answered(&group, Quorum::Everyone), "a");
+        assert_eq!(answered(&group, Quorum::AtLeast(1)), "abc");
+        assert_eq!(answered(&group, Quorum::AtLeast(3)), "a");
+    }
+
+    #[test]
+    fn test_at_least() {
+        let group = Group::parse("abcd\nabc\nab\na").unwrap();
+
+        assert_eq!(answered(&group, Quorum::AtLeast(0)), "abcd");
+        assert_eq!(answered(&group, Quorum::AtLeast(2)), "abc");
+        assert_eq!(answered(&group, Quorum::AtLeast(5)), "");
+    }
+
+    #[test]
+    fn test_percent() {
+        let group = Group::parse("abcd\nabc\nab\na").unwrap();
+
+        assert_eq!(answered(&group, Quorum::Percent(0.0)), "abcd");
+        assert_eq!(answered(&group, Quorum::Percent(50.0)), "abc");
+        assert_eq!(answered(&group, Quorum::Percent(51.0)), "ab");
+        assert_eq!(answered(&group, Quorum::Percent(100.0)), "a");
+
+        let group = Group::parse("a\na\na\na\na\na\na\nb\nb\nb").unwrap();
+        assert_eq!(answered(&group, Quorum::Percent(70.0)), "a");
+    }
+
+    #[test]
+    fn test_empty_group() {
+        let group = Group::parse("").unwrap();
+
+        assert!(group.is_empty());
+        assert!(group.answered_by(Quorum::Anyone).is_empty());
+        assert!(group.answered_by(Quorum::Everyone).is_empty());
+        assert!(group.answered_by(Quorum::Percent(0.0)).is_empty());
+    }
+}
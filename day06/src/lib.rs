@@ -0,0 +1,285 @@
+pub mod analytics;
+pub mod group;
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+
+use group::{Group, Quorum};
+
+/// The set of questions someone answered "yes" to.
+///
+/// Questions `a` to `z` are stored as a bitmask; any other question switches
+/// the set over to a general `BTreeSet`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Declaration {
+    /// Only questions `a` to `z`, where bit `n` is question `'a' + n`
+    Letters(u32),
+
+    /// Contains at least one question outside `a` to `z`
+    Other(BTreeSet<char>),
+}
+
+impl Declaration {
+    pub fn new() -> Self {
+        Declaration::Letters(0)
+    }
+
+    /// Parse one person's answers, one question per character.
+    pub fn parse(person: &str) -> Result<Self, ParseError> {
+        let mut declaration = Self::new();
+
+        for (position, answer) in person.chars().enumerate() {
+            if answer.is_whitespace() || answer.is_control() {
+                return Err(ParseError {
+                    line: 0,
+                    position,
+                    answer,
+                });
+            }
+
+            declaration.insert(answer);
+        }
+
+        Ok(declaration)
+    }
+
+    /// Add a question; returns `false` if it was already present.
+    pub fn insert(&mut self, question: char) -> bool {
+        match (self, letter_bit(question)) {
+            (Declaration::Letters(bits), Some(bit)) => {
+                let inserted = *bits & bit == 0;
+                *bits |= bit;
+                inserted
+            }
+
+            (Declaration::Other(set), _) => set.insert(question),
+
+            (this, None) => {
+                let mut set: BTreeSet<char> = this.iter().collect();
+                set.insert(question);
+                *this = Declaration::Other(set);
+                true
+            }
+        }
+    }
+
+    pub fn contains(&self, question: char) -> bool {
+        match (self, letter_bit(question)) {
+            (Declaration::Letters(bits), Some(bit)) => bits & bit != 0,
+            (Declaration::Letters(_), None) => false,
+            (Declaration::Other(set), _) => set.contains(&question),
+        }
+    }
+
+    /// Number of questions answered
+    pub fn len(&self) -> usize {
+        match self {
+            Declaration::Letters(bits) => bits.count_ones() as usize,
+            Declaration::Other(set) => set.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The questions answered, in order
+    pub fn iter(&self) -> Box<dyn Iterator<Item = char> + '_> {
+        match self {
+            Declaration::Letters(bits) => {
+                Box::new(('a'..='z').filter(move |&c| bits & letter_bit(c).unwrap() != 0))
+            }
+            Declaration::Other(set) => Box::new(set.iter().copied()),
+        }
+    }
+
+    /// Questions answered in either declaration
+    pub fn union(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Declaration::Letters(a), Declaration::Letters(b)) => Declaration::Letters(a | b),
+            _ => Declaration::Other(self.iter().chain(other.iter()).collect()),
+        }
+    }
+
+    /// Questions answered in both declarations
+    pub fn intersection(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Declaration::Letters(a), Declaration::Letters(b)) => Declaration::Letters(a & b),
+            _ => self.iter().filter(|&c| other.contains(c)).collect(),
+        }
+    }
+}
+
+impl Default for Declaration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::iter::FromIterator<char> for Declaration {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        let mut declaration = Self::new();
+
+        for question in iter {
+            declaration.insert(question);
+        }
+
+        declaration
+    }
+}
+
+/// The bit for questions `a` to `z`
+fn letter_bit(question: char) -> Option<u32> {
+    if question.is_ascii_lowercase() {
+        Some(1 << (question as u8 - b'a'))
+    } else {
+        None
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Line within the parsed text, starting at zero
+    pub line: usize,
+
+    /// Character within the line, starting at zero
+    pub position: usize,
+
+    pub answer: char,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid answer {:?} on line {}, position {}",
+            self.answer, self.line, self.position
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+/// Parse each person's declaration in a group, one person per line.
+/// Blank lines don't count as people.
+pub fn parse_people(group: &str) -> Result<Vec<Declaration>, ParseError> {
+    group
+        .lines()
+        .enumerate()
+        .filter(|(_, person)| !person.is_empty())
+        .map(|(line, person)| Declaration::parse(person).map_err(|e| ParseError { line, ..e }))
+        .collect()
+}
+
+/// Questions anyone in the group answered
+pub fn parse_group(group: &str) -> Result<Declaration, ParseError> {
+    Ok(Group::parse(group)?.answered_by(Quorum::Anyone))
+}
+
+/// Questions everyone in the group answered
+pub fn parse_group_part2(group: &str) -> Result<Declaration, ParseError> {
+    Ok(Group::parse(group)?.answered_by(Quorum::Everyone))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_letters_stay_bitmask() {
+        let declaration = Declaration::parse("abcz").unwrap();
+
+        assert_eq!(
+            declaration,
+            Declaration::Letters(0b10_0000_0000_0000_0000_0000_0111)
+        );
+        assert_eq!(declaration.len(), 4);
+    }
+
+    #[test]
+    fn test_other_questions() {
+        let declaration = Declaration::parse("aZ9é").unwrap();
+
+        assert!(matches!(declaration, Declaration::Other(_)));
+        assert_eq!(declaration.iter().collect::<String>(), "9Zaé");
+        assert!(declaration.contains('é'));
+        assert!(!declaration.contains('b'));
+    }
+
+    #[test]
+    fn test_invalid_answer() {
+        assert_eq!(
+            parse_group("abc\na c"),
+            Err(ParseError {
+                line: 1,
+                position: 1,
+                answer: ' '
+            })
+        );
+    }
+
+    #[test]
+    fn test_intersection_goes_back_to_bitmask() {
+        let group = parse_group_part2("abX\nbcX\nb").unwrap();
+
+        assert_eq!(group, Declaration::parse("b").unwrap());
+    }
+
+    #[test]
+    fn test_mixed_union_and_intersection() {
+        let a = Declaration::parse("ab!").unwrap();
+        let b = Declaration::parse("b!?").unwrap();
+
+        assert_eq!(a.union(&b).iter().collect::<String>(), "!?ab");
+        assert_eq!(a.intersection(&b).iter().collect::<String>(), "!b");
+    }
+
+    const EXAMPLE_GROUPS: [&str; 5] = ["abc", "a\nb\nc", "ab\nac", "a\na\na\na", "b"];
+
+    #[test]
+    fn test_example_part1() {
+        let counts: Vec<_> = EXAMPLE_GROUPS
+            .iter()
+            .map(|g| parse_group(g).unwrap().len())
+            .collect();
+
+        assert_eq!(counts, vec![3, 3, 3, 1, 1]);
+    }
+
+    #[test]
+    fn test_example_part2() {
+        let counts: Vec<_> = EXAMPLE_GROUPS
+            .iter()
+            .map(|g| parse_group_part2(g).unwrap().len())
+            .collect();
+
+        assert_eq!(counts, vec![3, 0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_empty_group() {
+        assert_eq!(parse_people(""), Ok(vec![]));
+        assert!(parse_group("").unwrap().is_empty());
+        assert!(parse_group_part2("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_single_person_group() {
+        let person = Declaration::parse("xyz").unwrap();
+
+        assert_eq!(parse_group("xyz"), Ok(person.clone()));
+        assert_eq!(parse_group_part2("xyz"), Ok(person));
+    }
+
+    #[test]
+    fn test_trailing_newline() {
+        assert_eq!(parse_people("ab\nac\n"), parse_people("ab\nac"));
+        assert_eq!(parse_group_part2("ab\nac\n\n").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_empty_person() {
+        assert!(Declaration::parse("").unwrap().is_empty());
+    }
+}
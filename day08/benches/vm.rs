@@ -0,0 +1,239 @@
+//! Compares `Vm::run`, which runs the puzzle's programs a basic block at a
+//! time, with the `Vm` it replaced, with running a step at a time, and with
+//! the reference interpreter. Also compares repairing programs by flipping
+//! one instruction, which runs the same program with many different patches.
+//!
+//! The reference interpreter only says where a loop starts, while `Vm::run`
+//! also lists the addresses round it and leading to it. On the puzzle input,
+//! whose blocks are under three instructions long on average, that's much of
+//! what's left between them.
+//!
+//! Run with `cargo bench`.
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use day08::generator::{GeneratorConfig, ProgramGenerator, Shape};
+use day08::repair::{repair, Mutation};
+use day08::{parse_instructions, reference, Instruction, Vm};
+
+/// How many times each program is run per measurement
+const RUNS: u32 = 20;
+
+/// The `Vm` before it was compiled: a `HashSet` of visited addresses, run a
+/// step at a time, recording the path to report loops with. This is just
+/// the part of it `run` used for the puzzle's instructions.
+struct Baseline<'a> {
+    program: &'a [Instruction],
+    patches: Vec<(usize, Instruction)>,
+    pc: usize,
+    acc: i32,
+    visited: HashSet<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum BaselineOutcome {
+    Terminated,
+    Looped {
+        body: Vec<usize>,
+        prefix: Vec<usize>,
+    },
+    Faulted,
+}
+
+impl<'a> Baseline<'a> {
+    fn new(program: &'a [Instruction]) -> Self {
+        Self {
+            program,
+            patches: vec![],
+            pc: 0,
+            acc: 0,
+            visited: HashSet::new(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.pc = 0;
+        self.acc = 0;
+        self.visited.clear();
+    }
+
+    fn instruction(&self, address: usize) -> Instruction {
+        match self.patches.iter().find(|&&(a, _)| a == address) {
+            Some(&(_, ins)) => ins,
+            None => self.program[address],
+        }
+    }
+
+    fn run(&mut self) -> BaselineOutcome {
+        let mut path = vec![];
+
+        loop {
+            if self.pc >= self.program.len() {
+                return BaselineOutcome::Terminated;
+            }
+
+            if !self.visited.insert(self.pc) {
+                let start = path.iter().position(|&a| a == self.pc).unwrap_or(0);
+                return BaselineOutcome::Looped {
+                    body: path[start..].to_vec(),
+                    prefix: path[..start].to_vec(),
+                };
+            }
+
+            let offset = match self.instruction(self.pc) {
+                Instruction::Acc(x) => match self.acc.checked_add(x) {
+                    Some(sum) => {
+                        self.acc = sum;
+                        1
+                    }
+                    None => return BaselineOutcome::Faulted,
+                },
+                Instruction::Jmp(x) => x,
+                _ => 1,
+            };
+
+            let target = self.pc as isize + offset;
+            if target < 0 || target as usize > self.program.len() {
+                return BaselineOutcome::Faulted;
+            }
+
+            path.push(self.pc);
+            self.pc = target as usize;
+        }
+    }
+
+    /// Every address where flipping the instruction makes the program
+    /// terminate, patching the same `Baseline` for each one
+    fn flip_repairs(&mut self) -> Vec<usize> {
+        let mut repairs = vec![];
+
+        for address in 0..self.program.len() {
+            if let Some(flipped) = self.program[address].flipped() {
+                self.patches = vec![(address, flipped)];
+                self.reset();
+
+                if self.run() == BaselineOutcome::Terminated {
+                    repairs.push(address);
+                }
+            }
+        }
+
+        repairs
+    }
+}
+
+fn time<F: FnMut()>(runs: u32, mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..runs {
+        f();
+    }
+    start.elapsed() / runs
+}
+
+fn bench_run(name: &str, program: &[Instruction]) {
+    let mut vm = Vm::borrowed(program);
+
+    // Compiling happens once per program, not once per run
+    vm.run();
+
+    let compiled = time(RUNS, || {
+        vm.reset();
+        vm.run();
+    });
+
+    // Tracing makes `run` go a step at a time
+    let stepped = time(RUNS, || {
+        vm.reset();
+        vm.enable_trace();
+        vm.run();
+        vm.take_trace();
+    });
+
+    let mut baseline = Baseline::new(program);
+    let before = time(RUNS, || {
+        baseline.reset();
+        baseline.run();
+    });
+
+    let reference = time(RUNS, || {
+        reference::run(program);
+    });
+
+    println!(
+        "{:<24} {:>8} {:>12?} {:>12?} {:>12?} {:>12?}",
+        name,
+        program.len(),
+        compiled,
+        before,
+        stepped,
+        reference
+    );
+}
+
+fn bench_repair(name: &str, program: &[Instruction]) {
+    let runs = 3;
+
+    let compiled = time(runs, || {
+        repair(program, &[Mutation::Flip], 1);
+    });
+
+    let mut baseline = Baseline::new(program);
+    let before = time(runs, || {
+        baseline.flip_repairs();
+    });
+
+    println!(
+        "{:<24} {:>8} {:>12?} {:>12?}",
+        name,
+        program.len(),
+        compiled,
+        before
+    );
+}
+
+fn generate(shape: Shape, len: usize) -> Vec<Instruction> {
+    let config = GeneratorConfig {
+        seed: 48,
+        len: len..=len,
+        // Few jumps, so there are long straight runs of `acc`s
+        jmp_percent: 5,
+        nop_percent: 5,
+        max_acc: 10,
+        shape,
+        ..Default::default()
+    };
+
+    ProgramGenerator::new(config).next_program()
+}
+
+fn main() {
+    let input = parse_instructions(include_str!("../input.txt"));
+
+    println!(
+        "{:<24} {:>8} {:>12} {:>12} {:>12} {:>12}",
+        "run", "len", "compiled", "before", "stepped", "reference"
+    );
+
+    bench_run("puzzle input", &input);
+
+    let shapes = [
+        ("terminating", Shape::Terminating),
+        ("looping", Shape::Looping),
+    ];
+
+    for &(name, shape) in shapes.iter() {
+        for &len in [1_000, 100_000, 1_000_000].iter() {
+            bench_run(&format!("{} {}", name, len), &generate(shape, len));
+        }
+    }
+
+    println!();
+    println!(
+        "{:<24} {:>8} {:>12} {:>12}",
+        "flip repairs", "len", "compiled", "before"
+    );
+
+    bench_repair("puzzle input", &input);
+    bench_repair("looping 20000", &generate(Shape::Looping, 20_000));
+}
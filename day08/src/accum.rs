@@ -0,0 +1,369 @@
+//! Working out the accumulator without running the program.
+//!
+//! For the puzzle's instructions, where execution goes never depends on the
+//! accumulator, so the path from each address is fixed. Summarising every
+//! path once, sharing the work between paths that meet, gives the final
+//! accumulator from any address, and from that the result of every repair.
+
+use crate::cfg::{successor, Cfg};
+use crate::{Instruction, InstructionSet};
+
+/// Where the path from an address ends up.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PathEnd {
+    Terminates,
+
+    /// Reaches `entry` for a second time
+    Loops {
+        entry: usize,
+    },
+
+    /// Jumps out of range from `address`
+    Faults {
+        address: usize,
+    },
+}
+
+/// What happens when execution starts at an address.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PathSummary {
+    /// The total added to the accumulator before the path ends. For a loop,
+    /// this goes once round it. It's an `i64`, so this can be worked out
+    /// even where running the program would overflow.
+    pub acc: i64,
+
+    pub end: PathEnd,
+}
+
+/// A single `jmp`/`nop` swap which makes a program terminate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FlipRepair {
+    pub address: usize,
+
+    /// The accumulator once the repaired program terminates
+    pub acc: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccAnalysis {
+    /// One summary per address, including the end of the program
+    summaries: Vec<PathSummary>,
+
+    /// For each path that terminates, the lowest and highest the accumulator
+    /// gets along the way, relative to where it started
+    ranges: Vec<Option<(i64, i64)>>,
+}
+
+/// How much executing `ins` adds to the accumulator
+fn contribution(ins: Instruction) -> i64 {
+    match ins {
+        Instruction::Acc(x) => x as i64,
+        _ => 0,
+    }
+}
+
+/// Whether the accumulator stays an `i32` all the way through `range`, when
+/// it starts at `acc`
+fn fits(acc: i64, (low, high): (i64, i64)) -> bool {
+    acc + low >= i32::MIN as i64 && acc + high <= i32::MAX as i64
+}
+
+impl AccAnalysis {
+    /// Summarise the path from every address of `program`, in time linear in
+    /// its length. Returns `None` for programs using more than the puzzle's
+    /// instructions, where paths depend on register values.
+    pub fn new(program: &[Instruction]) -> Option<Self> {
+        if InstructionSet::required_by(program) > InstructionSet::V1 {
+            return None;
+        }
+
+        let cfg = Cfg::new(program);
+        let len = program.len();
+
+        let mut summaries: Vec<Option<PathSummary>> = vec![None; len + 1];
+        summaries[len] = Some(PathSummary {
+            acc: 0,
+            end: PathEnd::Terminates,
+        });
+
+        let mut ranges = vec![None; len + 1];
+        ranges[len] = Some((0, 0));
+
+        // Position of each address on the path currently being followed
+        let mut on_path: Vec<Option<usize>> = vec![None; len];
+
+        for start in 0..len {
+            let mut path = vec![];
+            let mut address = start;
+
+            // Follow the path until it meets a summarised address, itself, or
+            // a bad jump
+            let mut end = loop {
+                if let Some(summary) = summaries[address] {
+                    break summary;
+                }
+
+                if let Some(position) = on_path[address] {
+                    // Everything on the cycle goes once round it and back to
+                    // where it started
+                    let cycle = &path[position..];
+                    let acc = cycle.iter().map(|&a| contribution(program[a])).sum();
+
+                    for &a in cycle {
+                        summaries[a] = Some(PathSummary {
+                            acc,
+                            end: PathEnd::Loops { entry: a },
+                        });
+                    }
+
+                    path.truncate(position);
+                    break summaries[address].unwrap();
+                }
+
+                on_path[address] = Some(path.len());
+                path.push(address);
+
+                match cfg.successor(address) {
+                    Some(next) => address = next,
+                    None => {
+                        path.pop();
+                        let summary = PathSummary {
+                            acc: contribution(program[address]),
+                            end: PathEnd::Faults { address },
+                        };
+                        summaries[address] = Some(summary);
+                        break summary;
+                    }
+                }
+            };
+
+            // Everything before the path ended leads into it
+            let mut range = ranges[address];
+
+            for &a in path.iter().rev() {
+                let acc = contribution(program[a]);
+                end.acc += acc;
+                summaries[a] = Some(end);
+
+                range = range.map(|(low, high)| ((acc + low).min(0), (acc + high).max(0)));
+                ranges[a] = range;
+            }
+
+            for a in path {
+                on_path[a] = None;
+            }
+        }
+
+        Some(Self {
+            summaries: summaries.into_iter().map(Option::unwrap).collect(),
+            ranges,
+        })
+    }
+
+    /// What happens from `address`, which may be the end of the program
+    pub fn summary(&self, address: usize) -> PathSummary {
+        self.summaries[address]
+    }
+
+    /// The final accumulator if the program terminates
+    pub fn final_acc(&self) -> Option<i64> {
+        let summary = self.summaries[0];
+
+        if summary.end == PathEnd::Terminates {
+            Some(summary.acc)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the accumulator stays an `i32` on the path from `address`,
+    /// starting at `acc`, if that path terminates
+    fn terminates_without_overflow(&self, address: usize, acc: i64) -> bool {
+        self.ranges[address].is_some_and(|range| fits(acc, range))
+    }
+}
+
+/// Every `jmp`/`nop` swap that makes `program` terminate, in the order they
+/// are reached, with the accumulator it would finish on. Nothing is executed.
+///
+/// Only instructions on the path from address 0 can change anything, and
+/// once one is flipped, the rest of the path is the original path from its
+/// new successor. That path can't lead back through the earlier part (which
+/// never terminates), so it must terminate by itself.
+///
+/// Repairs which would overflow the accumulator are left out, since running
+/// them faults.
+///
+/// Returns an empty list if the program already terminates, and `None` for
+/// programs `AccAnalysis` can't handle, or which would terminate but for
+/// overflowing.
+pub fn flip_repairs(program: &[Instruction]) -> Option<Vec<FlipRepair>> {
+    let analysis = AccAnalysis::new(program)?;
+
+    if analysis.final_acc().is_some() {
+        if analysis.terminates_without_overflow(0, 0) {
+            return Some(vec![]);
+        }
+
+        // The path after a flip might come back through it
+        return None;
+    }
+
+    let mut repairs = vec![];
+    let mut acc_before = 0;
+
+    for address in Cfg::new(program).path_from_entry() {
+        let ins = program[address];
+
+        if let Some(flipped) = ins.flipped() {
+            if let Some(next) = successor(flipped, address, program.len()) {
+                let rest = analysis.summary(next);
+
+                if analysis.terminates_without_overflow(next, acc_before) {
+                    repairs.push(FlipRepair {
+                        address,
+                        acc: acc_before + rest.acc,
+                    });
+                }
+            }
+        }
+
+        acc_before += contribution(ins);
+
+        // Every later flip runs this far first, and overflows
+        if !fits(acc_before, (0, 0)) {
+            break;
+        }
+    }
+
+    Some(repairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::example;
+    use crate::generator::{GeneratorConfig, ProgramGenerator};
+    use crate::repair::{repair, Mutation};
+    use crate::Instruction::*;
+    use crate::{parse_instructions, Reg, Vm};
+
+    #[test]
+    fn test_summaries() {
+        let analysis = AccAnalysis::new(&example()).unwrap();
+
+        assert_eq!(
+            analysis.summary(0),
+            PathSummary {
+                acc: 5,
+                end: PathEnd::Loops { entry: 1 }
+            }
+        );
+        assert_eq!(
+            analysis.summary(3),
+            PathSummary {
+                acc: 5,
+                end: PathEnd::Loops { entry: 3 }
+            }
+        );
+        assert_eq!(
+            analysis.summary(5),
+            PathSummary {
+                acc: -94,
+                end: PathEnd::Loops { entry: 6 }
+            }
+        );
+        assert_eq!(
+            analysis.summary(8),
+            PathSummary {
+                acc: 6,
+                end: PathEnd::Terminates
+            }
+        );
+        assert_eq!(analysis.final_acc(), None);
+    }
+
+    #[test]
+    fn test_faults() {
+        let analysis = AccAnalysis::new(&[Acc(2), Jmp(-5)]).unwrap();
+
+        assert_eq!(
+            analysis.summary(0),
+            PathSummary {
+                acc: 2,
+                end: PathEnd::Faults { address: 1 }
+            }
+        );
+    }
+
+    #[test]
+    fn test_flip_repairs() {
+        assert_eq!(
+            flip_repairs(&example()),
+            Some(vec![FlipRepair { address: 7, acc: 8 }])
+        );
+        assert_eq!(flip_repairs(&[Acc(1)]), Some(vec![]));
+        assert_eq!(flip_repairs(&[Hlt]), None);
+        assert_eq!(AccAnalysis::new(&[Out(Reg::A)]), None);
+    }
+
+    #[test]
+    fn test_puzzle_input() {
+        let program = parse_instructions(include_str!("../input.txt"));
+
+        let mut vm = Vm::borrowed(&program);
+        vm.step_until_loop();
+
+        let analysis = AccAnalysis::new(&program).unwrap();
+        assert_eq!(analysis.summary(0).acc, vm.acc() as i64);
+        assert_eq!(flip_repairs(&program).unwrap()[0].acc, 2251);
+    }
+
+    #[test]
+    fn test_overflowing_repairs() {
+        let max = i32::MAX;
+
+        assert_eq!(flip_repairs(&[Acc(max), Acc(1), Jmp(0)]), Some(vec![]));
+
+        // Skipping the `acc -1` would overflow, but jumping back past it doesn't
+        assert_eq!(
+            flip_repairs(&[Acc(max), Nop(2), Acc(-1), Acc(1), Jmp(-3)]),
+            Some(vec![FlipRepair {
+                address: 4,
+                acc: max as i64
+            }])
+        );
+        assert_eq!(flip_repairs(&[Acc(max), Acc(1)]), None);
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let config = GeneratorConfig {
+            seed: 47,
+            ..Default::default()
+        };
+
+        let max = i32::MAX;
+        let overflowing = vec![
+            vec![Acc(max), Acc(1), Jmp(0)],
+            vec![Acc(-max), Nop(3), Acc(-2), Jmp(0), Acc(2), Acc(-1)],
+            vec![Acc(max), Jmp(2), Acc(1), Nop(-1), Acc(-1)],
+        ];
+
+        for program in ProgramGenerator::new(config).take(500).chain(overflowing) {
+            let expected: Vec<_> = repair(&program, &[Mutation::Flip], 1)
+                .into_iter()
+                .filter(|r| !r.edits.is_empty())
+                .map(|r| FlipRepair {
+                    address: r.edits[0].address(),
+                    acc: r.acc as i64,
+                })
+                .collect();
+
+            let mut repairs = flip_repairs(&program).unwrap();
+            repairs.sort_by_key(|r| r.address);
+
+            assert_eq!(repairs, expected, "{:?}", program);
+        }
+    }
+}
@@ -0,0 +1,409 @@
+//! Assembler for handheld programs.
+//!
+//! On top of the puzzle's `op ±n` lines, this accepts:
+//! - comments starting with `#` or `;`, and blank lines
+//! - labels, written `name:` on their own line or before an instruction
+//! - label names in place of a jump offset, e.g. `jmp loop_start`
+//!
+//! `assemble_with` also accepts the instructions of later instruction sets,
+//! which name registers `a` to `d`, e.g. `add b -2` or `jnz c loop_start`.
+//!
+//! A label at the very end of the program refers to the address just past the
+//! last instruction, so `jmp end` terminates.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use crate::{Instruction, InstructionSet, Reg};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    /// Line of the source the error is on, starting at one
+    pub line: usize,
+    pub kind: AsmErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    UnknownOpcode(String),
+    MissingArgument,
+    InvalidArgument(String),
+    UnexpectedToken(String),
+    InvalidLabel(String),
+    DuplicateLabel(String),
+    UndefinedLabel(String),
+
+    /// A label was used as the argument of an instruction that doesn't jump
+    LabelNotAllowed(String),
+
+    UnknownRegister(String),
+
+    /// The opcode exists, but only in a later instruction set than the one
+    /// being assembled for
+    Unsupported(String, InstructionSet),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use AsmErrorKind::*;
+
+        write!(f, "line {}: ", self.line)?;
+
+        match &self.kind {
+            UnknownOpcode(op) => write!(f, "unknown opcode `{}`", op),
+            MissingArgument => write!(f, "missing argument"),
+            InvalidArgument(arg) => write!(f, "invalid argument `{}`", arg),
+            UnexpectedToken(token) => write!(f, "unexpected `{}`", token),
+            InvalidLabel(label) => write!(f, "invalid label name `{}`", label),
+            DuplicateLabel(label) => write!(f, "label `{}` is defined twice", label),
+            UndefinedLabel(label) => write!(f, "label `{}` is not defined", label),
+            LabelNotAllowed(op) => write!(f, "`{}` takes a number, not a label", op),
+            UnknownRegister(reg) => write!(f, "unknown register `{}`", reg),
+            Unsupported(op, set) => write!(f, "`{}` needs instruction set {:?}", op, set),
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+/// A source line with an instruction on it, before its operands are checked
+struct Line<'a> {
+    line: usize,
+    op: &'a str,
+    operands: Vec<&'a str>,
+}
+
+/// The kinds of operand an instruction can take
+#[derive(Copy, Clone)]
+enum Operand {
+    /// A register name
+    Reg,
+
+    /// A number
+    Number,
+
+    /// A number or a label, stored as an offset relative to the instruction
+    Offset,
+}
+
+/// The operands `op` takes and the first instruction set it's in,
+/// or `None` if it isn't an opcode
+fn signature(op: &str) -> Option<(&'static [Operand], InstructionSet)> {
+    use InstructionSet::*;
+    use Operand::*;
+
+    let signature: (&'static [Operand], _) = match op {
+        "acc" => (&[Number], V1),
+        "jmp" | "nop" => (&[Offset], V1),
+        "add" | "mul" | "set" => (&[Reg, Number], V2),
+        "jz" | "jnz" => (&[Reg, Offset], V2),
+        "out" => (&[Reg], V2),
+        "hlt" => (&[], V2),
+        _ => return None,
+    };
+
+    Some(signature)
+}
+
+/// Assemble `source` into instructions, resolving labels to relative offsets.
+/// Only the puzzle's `acc`, `jmp` and `nop` are accepted; see `assemble_with`
+/// for later instruction sets.
+pub fn assemble(source: &str) -> Result<Vec<Instruction>, AsmError> {
+    assemble_with(source, InstructionSet::V1)
+}
+
+/// Assemble `source`, accepting any instruction in `set`.
+pub fn assemble_with(source: &str, set: InstructionSet) -> Result<Vec<Instruction>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut lines = vec![];
+
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let err = |kind| AsmError { line, kind };
+
+        // Strip comments
+        let text = match text.find(['#', ';']) {
+            Some(idx) => &text[..idx],
+            None => text,
+        };
+
+        let mut tokens = text.split_whitespace().peekable();
+
+        // Any number of labels may come first
+        while let Some(label) = tokens.peek().and_then(|t| t.strip_suffix(':')) {
+            if !is_label(label) {
+                return Err(err(AsmErrorKind::InvalidLabel(label.to_string())));
+            }
+
+            if labels.insert(label, lines.len()).is_some() {
+                return Err(err(AsmErrorKind::DuplicateLabel(label.to_string())));
+            }
+
+            tokens.next();
+        }
+
+        let op = match tokens.next() {
+            Some(op) => op,
+            None => continue,
+        };
+
+        lines.push(Line {
+            line,
+            op,
+            operands: tokens.collect(),
+        });
+    }
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(address, line)| {
+            let err = |kind| AsmError {
+                line: line.line,
+                kind,
+            };
+
+            let (operands, needs) = signature(line.op)
+                .ok_or_else(|| err(AsmErrorKind::UnknownOpcode(line.op.to_string())))?;
+
+            if needs > set {
+                return Err(err(AsmErrorKind::Unsupported(line.op.to_string(), needs)));
+            }
+
+            if let Some(extra) = line.operands.get(operands.len()) {
+                return Err(err(AsmErrorKind::UnexpectedToken(extra.to_string())));
+            }
+
+            if line.operands.len() < operands.len() {
+                return Err(err(AsmErrorKind::MissingArgument));
+            }
+
+            let mut reg = Reg::ACC;
+            let mut value = 0;
+
+            for (&kind, &text) in operands.iter().zip(line.operands.iter()) {
+                match kind {
+                    Operand::Reg => {
+                        reg = Reg::from_name(text)
+                            .ok_or_else(|| err(AsmErrorKind::UnknownRegister(text.to_string())))?;
+                    }
+
+                    Operand::Number | Operand::Offset => {
+                        value = if is_number(text) {
+                            text.parse()
+                                .map_err(|_| err(AsmErrorKind::InvalidArgument(text.to_string())))?
+                        } else if !is_label(text) {
+                            return Err(err(AsmErrorKind::InvalidArgument(text.to_string())));
+                        } else if let Operand::Number = kind {
+                            return Err(err(AsmErrorKind::LabelNotAllowed(line.op.to_string())));
+                        } else {
+                            match labels.get(text) {
+                                Some(&target) => target as i64 - address as i64,
+                                None => {
+                                    return Err(err(AsmErrorKind::UndefinedLabel(text.to_string())))
+                                }
+                            }
+                        };
+                    }
+                }
+            }
+
+            let invalid_arg = || err(AsmErrorKind::InvalidArgument(value.to_string()));
+            let number = || i32::try_from(value).map_err(|_| invalid_arg());
+            // Offsets are limited to what the bytecode format can store
+            let offset = || number().map(|offset| offset as isize);
+
+            Ok(match line.op {
+                "acc" => Instruction::Acc(number()?),
+                "jmp" => Instruction::Jmp(offset()?),
+                "nop" => Instruction::Nop(offset()?),
+                "add" => Instruction::Add(reg, number()?),
+                "mul" => Instruction::Mul(reg, number()?),
+                "set" => Instruction::Set(reg, number()?),
+                "jz" => Instruction::Jz(reg, offset()?),
+                "jnz" => Instruction::Jnz(reg, offset()?),
+                "out" => Instruction::Out(reg),
+                "hlt" => Instruction::Hlt,
+                _ => unreachable!("every opcode has a signature"),
+            })
+        })
+        .collect()
+}
+
+fn is_number(s: &str) -> bool {
+    s.starts_with(|c: char| c == '+' || c == '-' || c.is_ascii_digit())
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Instruction::*;
+
+    #[test]
+    fn test_plain_program() {
+        let input = "nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6";
+
+        assert_eq!(
+            assemble(input).unwrap(),
+            vec![
+                Nop(0),
+                Acc(1),
+                Jmp(4),
+                Acc(3),
+                Jmp(-3),
+                Acc(-99),
+                Acc(1),
+                Jmp(-4),
+                Acc(6)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_labels_and_comments() {
+        let input = "\
+# count up forever
+start:
+    acc +1      ; bump
+loop_start: nop end
+    jmp loop_start
+
+    jmp start
+end:
+";
+
+        assert_eq!(
+            assemble(input).unwrap(),
+            vec![Acc(1), Nop(3), Jmp(-1), Jmp(-3)]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |source: &str| assemble(source).unwrap_err();
+
+        assert_eq!(
+            err("nop +0\nfoo +1"),
+            AsmError {
+                line: 2,
+                kind: AsmErrorKind::UnknownOpcode("foo".to_string())
+            }
+        );
+        assert_eq!(err("jmp").kind, AsmErrorKind::MissingArgument);
+        assert_eq!(
+            err("acc +1 +2").kind,
+            AsmErrorKind::UnexpectedToken("+2".to_string())
+        );
+        assert_eq!(
+            err("acc +x").kind,
+            AsmErrorKind::InvalidArgument("+x".to_string())
+        );
+        assert_eq!(
+            err("a:\na: nop +0").kind,
+            AsmErrorKind::DuplicateLabel("a".to_string())
+        );
+        assert_eq!(
+            err("\n\njmp nowhere"),
+            AsmError {
+                line: 3,
+                kind: AsmErrorKind::UndefinedLabel("nowhere".to_string())
+            }
+        );
+        assert_eq!(
+            err("x: acc x").kind,
+            AsmErrorKind::LabelNotAllowed("acc".to_string())
+        );
+        assert_eq!(
+            err("1x: nop +0").kind,
+            AsmErrorKind::InvalidLabel("1x".to_string())
+        );
+        assert_eq!(
+            err("acc +3000000000").kind,
+            AsmErrorKind::InvalidArgument("3000000000".to_string())
+        );
+        assert_eq!(
+            err("jmp +2147483648").kind,
+            AsmErrorKind::InvalidArgument("2147483648".to_string())
+        );
+        assert_eq!(
+            err("nop -2147483649").kind,
+            AsmErrorKind::InvalidArgument("-2147483649".to_string())
+        );
+        assert_eq!(
+            assemble("jmp -2147483648").unwrap(),
+            vec![Jmp(i32::MIN as isize)]
+        );
+        assert_eq!(
+            err("out a"),
+            AsmError {
+                line: 1,
+                kind: AsmErrorKind::Unsupported("out".to_string(), InstructionSet::V2)
+            }
+        );
+    }
+
+    #[test]
+    fn test_v2_program() {
+        let input = "\
+    set b +3
+loop:
+    add a +2
+    add b -1
+    jnz b loop
+    mul a -1
+    out a
+    jz c end
+    hlt
+end:
+";
+
+        let a = Reg::A;
+        let b = Reg::B;
+
+        assert_eq!(
+            assemble_with(input, InstructionSet::V2).unwrap(),
+            vec![
+                Set(b, 3),
+                Add(a, 2),
+                Add(b, -1),
+                Jnz(b, -2),
+                Mul(a, -1),
+                Out(a),
+                Jz(Reg::C, 2),
+                Hlt
+            ]
+        );
+    }
+
+    #[test]
+    fn test_v2_errors() {
+        let err = |source: &str| assemble_with(source, InstructionSet::V2).unwrap_err();
+
+        assert_eq!(
+            err("add e +1").kind,
+            AsmErrorKind::UnknownRegister("e".to_string())
+        );
+        assert_eq!(err("jnz a").kind, AsmErrorKind::MissingArgument);
+        assert_eq!(
+            err("hlt +0").kind,
+            AsmErrorKind::UnexpectedToken("+0".to_string())
+        );
+        assert_eq!(
+            err("x: set a x").kind,
+            AsmErrorKind::LabelNotAllowed("set".to_string())
+        );
+    }
+}
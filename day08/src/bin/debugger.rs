@@ -0,0 +1,55 @@
+//! Interactive debugger for handheld programs.
+//!
+//! Usage: `debugger [FILE]`, debugging the puzzle input if no file is given.
+//! Type `help` at the prompt for the list of commands.
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use day08::asm::assemble_with;
+use day08::debugger::{Debugger, Response};
+use day08::InstructionSet;
+
+const INPUT: &str = include_str!("../../input.txt");
+
+fn main() {
+    let source = match env::args().nth(1) {
+        Some(path) => fs::read_to_string(&path).unwrap_or_else(|e| {
+            eprintln!("couldn't read {}: {}", path, e);
+            process::exit(1);
+        }),
+        None => INPUT.to_string(),
+    };
+
+    let program = assemble_with(&source, InstructionSet::LATEST).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    println!(
+        "loaded {} instructions; type `help` for commands",
+        program.len()
+    );
+    let mut debugger = Debugger::new(program);
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        print!("(hh) ");
+        io::stdout().flush().unwrap();
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+
+        match debugger.execute(&line) {
+            Response::Output(output) if output.is_empty() => {}
+            Response::Output(output) => println!("{}", output),
+            Response::Quit => break,
+        }
+    }
+}
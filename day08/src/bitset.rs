@@ -0,0 +1,181 @@
+//! A fixed-size set of addresses, one bit each.
+
+/// The set remembers which words it has touched, so that clearing it and
+/// checking whether it's empty cost nothing for a run that only visited a
+/// small part of a long program.
+#[derive(Clone, Debug)]
+pub(crate) struct BitSet {
+    words: Vec<u64>,
+
+    /// Every word outside `dirty.0..dirty.1` is zero
+    dirty: (usize, usize),
+}
+
+/// A dirty range that any touched word widens
+const CLEAN: (usize, usize) = (usize::MAX, 0);
+
+impl BitSet {
+    /// A set that can hold `0..len`
+    pub(crate) fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+            dirty: CLEAN,
+        }
+    }
+
+    /// Note that the words `first..=last` may have changed
+    fn touch(&mut self, first: usize, last: usize) {
+        self.dirty.0 = self.dirty.0.min(first);
+        self.dirty.1 = self.dirty.1.max(last + 1);
+    }
+
+    /// Add `i`, returning whether it wasn't there already
+    pub(crate) fn insert(&mut self, i: usize) -> bool {
+        self.touch(i / 64, i / 64);
+
+        let word = &mut self.words[i / 64];
+        let bit = 1 << (i % 64);
+        let new = *word & bit == 0;
+
+        *word |= bit;
+        new
+    }
+
+    /// Whether `i` is in the set. Anything past the end never is.
+    pub(crate) fn contains(&self, i: usize) -> bool {
+        match self.words.get(i / 64) {
+            Some(word) => word & (1 << (i % 64)) != 0,
+            None => false,
+        }
+    }
+
+    /// Add every number in `start..end` before the smallest one that's
+    /// already in the set, and return that one. Finding it and adding the
+    /// rest is a single pass over the words.
+    pub(crate) fn insert_until_repeat(&mut self, start: usize, end: usize) -> Option<usize> {
+        if start >= end {
+            return None;
+        }
+
+        // Either something is inserted, or the set already has the repeat,
+        // so the touched words aren't all empty
+        self.touch(start / 64, (end - 1) / 64);
+
+        let mut i = start;
+
+        while i < end {
+            let bits = (end - i).min(64 - i % 64);
+            let mask = mask(i % 64, bits);
+            let word = &mut self.words[i / 64];
+            let found = *word & mask;
+
+            if found != 0 {
+                let repeat = found.trailing_zeros();
+                *word |= mask & ((1 << repeat) - 1);
+                return Some(i / 64 * 64 + repeat as usize);
+            }
+
+            *word |= mask;
+            i += bits;
+        }
+
+        None
+    }
+
+    pub(crate) fn clear(&mut self) {
+        let (start, end) = self.dirty;
+
+        if start < end {
+            for word in self.words[start..end].iter_mut() {
+                *word = 0;
+            }
+        }
+
+        self.dirty = CLEAN;
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        // Bits are only ever removed all at once, so every touched word has
+        // something in it
+        self.dirty.0 >= self.dirty.1
+    }
+
+    /// Everything in the set, in increasing order
+    pub(crate) fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(w, &word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| w * 64 + bit)
+        })
+    }
+}
+
+impl PartialEq for BitSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.words == other.words
+    }
+}
+
+impl Eq for BitSet {}
+
+/// `bits` one bits, starting at bit `shift`. `bits` mustn't be zero.
+fn mask(shift: usize, bits: usize) -> u64 {
+    !0 >> (64 - bits) << shift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitset() {
+        let mut set = BitSet::new(130);
+        assert!(set.is_empty());
+
+        assert!(set.insert(3));
+        assert!(!set.insert(3));
+        assert!(set.insert(129));
+        set.insert_until_repeat(62, 66);
+        set.insert_until_repeat(66, 66);
+
+        assert!(set.contains(64));
+        assert!(!set.contains(66));
+        assert!(!set.contains(1000));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![3, 62, 63, 64, 65, 129]);
+
+        set.clear();
+        assert!(set.is_empty());
+        assert_eq!(set, BitSet::new(130));
+
+        set.insert_until_repeat(1, 129);
+        assert_eq!(set.iter().count(), 128);
+        assert!(!set.contains(0) && !set.contains(129));
+    }
+
+    #[test]
+    fn test_insert_until_repeat() {
+        let mut set = BitSet::new(200);
+        set.insert(5);
+        set.insert(70);
+        set.insert(128);
+
+        assert_eq!(set.insert_until_repeat(10, 10), None);
+        assert_eq!(set.insert_until_repeat(60, 200), Some(70));
+        assert_eq!(set.insert_until_repeat(0, 200), Some(5));
+        assert_eq!(set.insert_until_repeat(71, 128), None);
+        assert_eq!(set.insert_until_repeat(129, 131), None);
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4, 5]
+                .iter()
+                .copied()
+                .chain(60..=130)
+                .collect::<Vec<_>>()
+        );
+
+        let mut set = BitSet::new(200);
+        set.insert(199);
+        assert_eq!(set.insert_until_repeat(0, 200), Some(199));
+        assert_eq!(set.iter().count(), 200);
+    }
+}
@@ -0,0 +1,323 @@
+//! A compact binary encoding of programs.
+//!
+//! Every program starts with a 10 byte header:
+//!
+//! ```text
+//! magic    4 bytes   "HHBC"
+//! version  u16 LE    an instruction set the program runs on
+//! count    u32 LE    the number of instructions
+//! ```
+//!
+//! followed by 6 bytes per instruction: an opcode, a register (zero if the
+//! instruction doesn't take one), and an argument as an `i32` LE (zero if it
+//! doesn't take one).
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use crate::{Instruction, InstructionSet, Reg};
+
+pub const MAGIC: [u8; 4] = *b"HHBC";
+
+pub const HEADER_LEN: usize = 10;
+
+pub const INSTRUCTION_LEN: usize = 6;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoadError {
+    /// Fewer bytes than a header
+    TooShort,
+    BadMagic,
+    UnsupportedVersion(u16),
+
+    /// The header's count doesn't match the amount of data after it
+    WrongLength {
+        expected: usize,
+        found: usize,
+    },
+
+    /// An instruction (counting from zero) has an opcode that doesn't exist
+    UnknownOpcode {
+        index: usize,
+        opcode: u8,
+    },
+
+    UnknownRegister {
+        index: usize,
+        reg: u8,
+    },
+
+    /// An instruction isn't in the instruction set the header says
+    Unsupported {
+        index: usize,
+        set: InstructionSet,
+    },
+
+    /// An instruction has a register or argument it doesn't take
+    UnusedOperand {
+        index: usize,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use LoadError::*;
+
+        match *self {
+            TooShort => write!(f, "too short for a header"),
+            BadMagic => write!(f, "not a bytecode file"),
+            UnsupportedVersion(v) => write!(f, "unsupported version {}", v),
+            WrongLength { expected, found } => {
+                write!(f, "expected {} bytes, found {}", expected, found)
+            }
+            UnknownOpcode { index, opcode } => {
+                write!(f, "instruction {}: unknown opcode {}", index, opcode)
+            }
+            UnknownRegister { index, reg } => {
+                write!(f, "instruction {}: unknown register {}", index, reg)
+            }
+            Unsupported { index, set } => {
+                write!(f, "instruction {}: not in instruction set {:?}", index, set)
+            }
+            UnusedOperand { index } => {
+                write!(f, "instruction {}: operand set that isn't used", index)
+            }
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+fn opcode(ins: Instruction) -> u8 {
+    match ins {
+        Instruction::Acc(_) => 0,
+        Instruction::Jmp(_) => 1,
+        Instruction::Nop(_) => 2,
+        Instruction::Add(..) => 3,
+        Instruction::Mul(..) => 4,
+        Instruction::Set(..) => 5,
+        Instruction::Jz(..) => 6,
+        Instruction::Jnz(..) => 7,
+        Instruction::Out(_) => 8,
+        Instruction::Hlt => 9,
+    }
+}
+
+/// Encode `program`, or return `None` if a jump offset doesn't fit in an `i32`
+/// or there are more than `u32::MAX` instructions.
+pub fn encode(program: &[Instruction]) -> Option<Vec<u8>> {
+    let count = u32::try_from(program.len()).ok()?;
+    let version = InstructionSet::required_by(program).version();
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + INSTRUCTION_LEN * program.len());
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&version.to_le_bytes());
+    bytes.extend_from_slice(&count.to_le_bytes());
+
+    for &ins in program {
+        let offset = |x: isize| i32::try_from(x).ok();
+
+        let (reg, arg) = match ins {
+            Instruction::Acc(x) => (None, x),
+            Instruction::Jmp(x) | Instruction::Nop(x) => (None, offset(x)?),
+            Instruction::Add(r, x) | Instruction::Mul(r, x) | Instruction::Set(r, x) => {
+                (Some(r), x)
+            }
+            Instruction::Jz(r, x) | Instruction::Jnz(r, x) => (Some(r), offset(x)?),
+            Instruction::Out(r) => (Some(r), 0),
+            Instruction::Hlt => (None, 0),
+        };
+
+        bytes.push(opcode(ins));
+        bytes.push(reg.map_or(0, |r| r.index() as u8));
+        bytes.extend_from_slice(&arg.to_le_bytes());
+    }
+
+    Some(bytes)
+}
+
+/// Decode and validate a program written by `encode`.
+pub fn load(bytes: &[u8]) -> Result<Vec<Instruction>, LoadError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(LoadError::TooShort);
+    }
+
+    if bytes[0..4] != MAGIC {
+        return Err(LoadError::BadMagic);
+    }
+
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    let set =
+        InstructionSet::from_version(version).ok_or(LoadError::UnsupportedVersion(version))?;
+
+    let count = u32::from_le_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]) as usize;
+    let expected = count
+        .checked_mul(INSTRUCTION_LEN)
+        .and_then(|len| len.checked_add(HEADER_LEN));
+
+    if expected != Some(bytes.len()) {
+        return Err(LoadError::WrongLength {
+            expected: expected.unwrap_or(usize::MAX),
+            found: bytes.len(),
+        });
+    }
+
+    bytes[HEADER_LEN..]
+        .chunks_exact(INSTRUCTION_LEN)
+        .enumerate()
+        .map(|(index, record)| {
+            let ins = decode_instruction(index, record)?;
+
+            if !set.supports(ins) {
+                return Err(LoadError::Unsupported { index, set });
+            }
+
+            Ok(ins)
+        })
+        .collect()
+}
+
+fn decode_instruction(index: usize, record: &[u8]) -> Result<Instruction, LoadError> {
+    let opcode = record[0];
+    let arg = i32::from_le_bytes([record[2], record[3], record[4], record[5]]);
+
+    let reg = || {
+        Reg::new(record[1] as usize).ok_or(LoadError::UnknownRegister {
+            index,
+            reg: record[1],
+        })
+    };
+
+    // Operands an instruction doesn't take must be zero, so every instruction
+    // has exactly one encoding. The header's version needn't be the earliest
+    // that will do, since every later set runs the same programs.
+    let unused = |used: bool| {
+        if used {
+            Ok(())
+        } else {
+            Err(LoadError::UnusedOperand { index })
+        }
+    };
+
+    let ins = match opcode {
+        0..=2 => {
+            unused(record[1] == 0)?;
+
+            match opcode {
+                0 => Instruction::Acc(arg),
+                1 => Instruction::Jmp(arg as isize),
+                _ => Instruction::Nop(arg as isize),
+            }
+        }
+        3 => Instruction::Add(reg()?, arg),
+        4 => Instruction::Mul(reg()?, arg),
+        5 => Instruction::Set(reg()?, arg),
+        6 => Instruction::Jz(reg()?, arg as isize),
+        7 => Instruction::Jnz(reg()?, arg as isize),
+        8 => {
+            unused(arg == 0)?;
+            Instruction::Out(reg()?)
+        }
+        9 => {
+            unused(record[1] == 0 && arg == 0)?;
+            Instruction::Hlt
+        }
+        _ => return Err(LoadError::UnknownOpcode { index, opcode }),
+    };
+
+    Ok(ins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_instructions;
+    use crate::Instruction::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            encode(&[Acc(-2), Jnz(Reg::C, 3)]).unwrap(),
+            vec![
+                b'H', b'H', b'B', b'C', 2, 0, 2, 0, 0, 0, //
+                0, 0, 0xfe, 0xff, 0xff, 0xff, //
+                7, 2, 3, 0, 0, 0,
+            ]
+        );
+        assert_eq!(encode(&[Jmp(1 << 40)]), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let program = parse_instructions(include_str!("../input.txt"));
+        let bytes = encode(&program).unwrap();
+
+        assert_eq!(bytes.len(), HEADER_LEN + INSTRUCTION_LEN * program.len());
+        assert_eq!(&bytes[4..6], &[1, 0]);
+        assert_eq!(load(&bytes).unwrap(), program);
+
+        let program = vec![
+            Set(Reg::B, 3),
+            Mul(Reg::B, -7),
+            Jz(Reg::D, -1),
+            Out(Reg::A),
+            Hlt,
+            Nop(0),
+        ];
+        assert_eq!(load(&encode(&program).unwrap()).unwrap(), program);
+        assert_eq!(load(&encode(&[]).unwrap()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let valid = encode(&[Acc(1), Hlt]).unwrap();
+        let with = |i: usize, byte: u8| {
+            let mut bytes = valid.clone();
+            bytes[i] = byte;
+            load(&bytes).unwrap_err()
+        };
+
+        assert_eq!(load(&valid[..5]), Err(LoadError::TooShort));
+        assert_eq!(with(0, b'X'), LoadError::BadMagic);
+        assert_eq!(with(4, 7), LoadError::UnsupportedVersion(7));
+        assert_eq!(
+            load(&valid[..valid.len() - 1]),
+            Err(LoadError::WrongLength {
+                expected: 22,
+                found: 21
+            })
+        );
+        assert_eq!(
+            with(10, 200),
+            LoadError::UnknownOpcode {
+                index: 0,
+                opcode: 200
+            }
+        );
+        assert_eq!(with(11, 1), LoadError::UnusedOperand { index: 0 });
+
+        let mut bad_register = valid.clone();
+        bad_register[16] = 8;
+        bad_register[17] = 4;
+        assert_eq!(
+            load(&bad_register),
+            Err(LoadError::UnknownRegister { index: 1, reg: 4 })
+        );
+
+        // A V2 instruction in a V1 program
+        assert_eq!(
+            with(4, 1),
+            LoadError::Unsupported {
+                index: 1,
+                set: InstructionSet::V1
+            }
+        );
+
+        // A V1 program in a V2 header, as written by an encoder that always
+        // names the latest set
+        let mut v1 = encode(&[Acc(1)]).unwrap();
+        v1[4] = 2;
+        assert_eq!(load(&v1), Ok(vec![Acc(1)]));
+    }
+}
@@ -0,0 +1,255 @@
+//! Static control-flow analysis of handheld programs.
+//!
+//! The control-flow graph is over the addresses `0..=len`, where `len` means
+//! the program terminated. Without conditional jumps every instruction has
+//! exactly one successor, so the graph is a functional graph and each question
+//! below has an exact answer; `jz` and `jnz` have two, and the analysis treats
+//! either branch as possible.
+
+use std::collections::VecDeque;
+
+use crate::Instruction;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cfg {
+    /// The addresses that may be executed after each instruction, leaving out
+    /// jumps somewhere the program can't continue from (negative or past the end)
+    successors: Vec<Vec<usize>>,
+}
+
+/// Every address that may be executed after `ins` at `address`, leaving out
+/// jumps that are out of range, including those too far to add up. `len` is
+/// the address just past the last instruction, which is where `hlt` goes.
+pub fn successors(ins: Instruction, address: usize, len: usize) -> Vec<usize> {
+    let jump = |x: isize| (address as isize).checked_add(x);
+    let next = jump(1);
+
+    let targets = match ins {
+        Instruction::Hlt => vec![Some(len as isize)],
+        Instruction::Jmp(x) => vec![jump(x)],
+        Instruction::Jz(_, x) | Instruction::Jnz(_, x) => vec![next, jump(x)],
+        _ => vec![next],
+    };
+
+    let mut targets: Vec<_> = targets
+        .into_iter()
+        .flatten()
+        .filter(|&t| !t.is_negative() && t as usize <= len)
+        .map(|t| t as usize)
+        .collect();
+
+    targets.dedup();
+    targets
+}
+
+/// The address executed after `ins` at `address`, or `None` if the jump is
+/// out of range or `ins` is a conditional jump.
+pub fn successor(ins: Instruction, address: usize, len: usize) -> Option<usize> {
+    if ins.is_conditional() {
+        return None;
+    }
+
+    successors(ins, address, len).first().copied()
+}
+
+impl Cfg {
+    pub fn new(program: &[Instruction]) -> Self {
+        let successors = program
+            .iter()
+            .enumerate()
+            .map(|(address, &ins)| successors(ins, address, program.len()))
+            .collect();
+
+        Self { successors }
+    }
+
+    /// Number of instructions; also the address meaning "terminated"
+    pub fn len(&self) -> usize {
+        self.successors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.successors.is_empty()
+    }
+
+    /// The address executed after `address`, if there's exactly one
+    pub fn successor(&self, address: usize) -> Option<usize> {
+        match self.successors[address].as_slice() {
+            &[next] => Some(next),
+            _ => None,
+        }
+    }
+
+    pub fn successors(&self, address: usize) -> &[usize] {
+        &self.successors[address]
+    }
+
+    /// For each address (including the end), the instructions that lead to it
+    pub fn predecessors(&self) -> Vec<Vec<usize>> {
+        let mut predecessors = vec![vec![]; self.len() + 1];
+
+        for (address, successors) in self.successors.iter().enumerate() {
+            for &next in successors {
+                predecessors[next].push(address);
+            }
+        }
+
+        predecessors
+    }
+
+    /// For each address `0..=len`, whether execution starting there terminates.
+    /// With conditional jumps this means it terminates for some register values.
+    ///
+    /// This searches backwards from the end, so it's linear in the program size.
+    pub fn terminating(&self) -> Vec<bool> {
+        let predecessors = self.predecessors();
+        let mut terminating = vec![false; self.len() + 1];
+        let mut queue = VecDeque::new();
+
+        terminating[self.len()] = true;
+        queue.push_back(self.len());
+
+        while let Some(address) = queue.pop_front() {
+            for &prev in predecessors[address].iter() {
+                if !terminating[prev] {
+                    terminating[prev] = true;
+                    queue.push_back(prev);
+                }
+            }
+        }
+
+        terminating
+    }
+
+    /// The addresses executed starting from address 0, in order, up to (but
+    /// not including) the first repeated address, the end, or a bad jump.
+    /// A conditional jump is the last address on the path.
+    pub fn path_from_entry(&self) -> Vec<usize> {
+        let mut seen = vec![false; self.len()];
+        let mut path = vec![];
+        let mut address = Some(0);
+
+        while let Some(a) = address {
+            if a >= self.len() || seen[a] {
+                break;
+            }
+
+            seen[a] = true;
+            path.push(a);
+            address = self.successor(a);
+        }
+
+        path
+    }
+}
+
+/// Find the `jmp` or `nop` which, when swapped for the other, makes the
+/// program terminate from address 0. Returns the first such address along the
+/// execution path, or `None` if the program already terminates or no single
+/// swap helps. Programs with conditional jumps aren't supported, and also give
+/// `None`.
+///
+/// This runs in time linear in the program size, rather than re-running the
+/// program once per candidate instruction.
+pub fn find_repair(program: &[Instruction]) -> Option<usize> {
+    if program.iter().any(|ins| ins.is_conditional()) {
+        return None;
+    }
+
+    let cfg = Cfg::new(program);
+    let terminating = cfg.terminating();
+
+    if terminating[0] {
+        return None;
+    }
+
+    // Only instructions on the original path can change where it goes
+    cfg.path_from_entry().into_iter().find(|&address| {
+        program[address]
+            .flipped()
+            .and_then(|ins| successor(ins, address, program.len()))
+            .is_some_and(|next| terminating[next])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::example;
+    use crate::Instruction::*;
+    use crate::Reg;
+
+    #[test]
+    fn test_successors() {
+        let cfg = Cfg::new(&[Acc(1), Jmp(-5), Jmp(2), Nop(-10)]);
+
+        assert_eq!(cfg.successor(0), Some(1));
+        assert_eq!(cfg.successor(1), None);
+        assert_eq!(cfg.successor(2), Some(4));
+        assert_eq!(cfg.successor(3), Some(4));
+    }
+
+    #[test]
+    fn test_conditional_successors() {
+        let a = Reg::A;
+        let cfg = Cfg::new(&[Jz(a, 2), Jnz(a, -1), Hlt, Jz(a, 1)]);
+
+        assert_eq!(cfg.successors(0), &[1, 2]);
+        assert_eq!(cfg.successor(0), None);
+        assert_eq!(cfg.successors(1), &[2, 0]);
+        assert_eq!(cfg.successor(2), Some(4));
+        assert_eq!(cfg.successors(3), &[4]);
+        assert_eq!(cfg.terminating(), vec![true; 5]);
+        assert_eq!(cfg.path_from_entry(), vec![0]);
+    }
+
+    #[test]
+    fn test_terminating() {
+        let cfg = Cfg::new(&example());
+
+        assert_eq!(
+            cfg.terminating(),
+            vec![false, false, false, false, false, false, false, false, true, true]
+        );
+    }
+
+    #[test]
+    fn test_path_from_entry() {
+        let cfg = Cfg::new(&example());
+
+        assert_eq!(cfg.path_from_entry(), vec![0, 1, 2, 6, 7, 3, 4]);
+    }
+
+    #[test]
+    fn test_find_repair() {
+        assert_eq!(find_repair(&example()), Some(7));
+    }
+
+    #[test]
+    fn test_find_repair_already_terminates() {
+        assert_eq!(find_repair(&[Acc(1), Nop(0)]), None);
+    }
+
+    #[test]
+    fn test_find_repair_conditional() {
+        assert_eq!(find_repair(&[Jnz(Reg::A, 0), Jmp(-1)]), None);
+    }
+
+    #[test]
+    fn test_find_repair_none_possible() {
+        assert_eq!(find_repair(&[Jmp(0), Acc(1), Jmp(-1)]), None);
+    }
+
+    #[test]
+    fn test_overflowing_jumps() {
+        let cfg = Cfg::new(&[Jmp(isize::MAX), Jz(Reg::A, isize::MAX), Jmp(isize::MIN)]);
+
+        assert_eq!(cfg.successors(0), &[] as &[usize]);
+        assert_eq!(cfg.successors(1), &[2]);
+        assert_eq!(cfg.successors(2), &[] as &[usize]);
+
+        // Flipping the `nop` would jump too far, so only the `jmp` can be repaired
+        assert_eq!(find_repair(&[Nop(isize::MAX), Jmp(-1)]), Some(1));
+        assert_eq!(find_repair(&[Jmp(isize::MAX), Jmp(-1)]), None);
+    }
+}
@@ -0,0 +1,472 @@
+//! The form `Vm::run` executes the puzzle's programs in.
+//!
+//! The program is split into basic blocks, which end at a jump or just before
+//! a jump target. Each block's `acc`s are added up in advance and its jump
+//! target is resolved, so running a whole block is a single step however
+//! long it is. Blocks are stored one after another rather than one per
+//! address, so following short jumps between them stays in cache.
+//!
+//! Only the unpatched program is compiled, so patching doesn't cost a
+//! recompile. Patched addresses are run an instruction at a time, and the
+//! blocks around them are cut short just before them. Running part of a
+//! block uses running totals over the whole program instead.
+
+use crate::bitset::BitSet;
+use crate::{Cycle, Instruction, InstructionSet};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Block {
+    start: usize,
+
+    /// One past the block's last address
+    end: usize,
+
+    /// Total added to the accumulator
+    acc: i64,
+
+    /// Total size of the amounts added to the accumulator. While the
+    /// accumulator is at most `i32::MAX - magnitude` in size, nothing in the
+    /// block can overflow it.
+    magnitude: i64,
+
+    /// Where execution goes after the block, which may be past the end, or
+    /// `BAD_JUMP` if it's negative or too far to add up
+    next: usize,
+
+    /// The index of the block starting at `next`, if `next` is in the program
+    next_block: usize,
+}
+
+const BAD_JUMP: usize = usize::MAX;
+
+/// The address `offset` from `address`, or `BAD_JUMP`
+fn resolve(address: usize, offset: isize) -> usize {
+    match (address as isize).checked_add(offset) {
+        Some(target) if target >= 0 => target as usize,
+        _ => BAD_JUMP,
+    }
+}
+
+/// How a compiled run ended, with the address it stopped at
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Exit {
+    /// Went past the end, to this address
+    Terminated(usize),
+
+    /// Was about to execute this address again
+    Looped(usize),
+
+    /// Jumped out of range from this address
+    BadJump(usize),
+
+    /// The accumulator might overflow somewhere after this address, so it
+    /// needs running an instruction at a time
+    MightOverflow(usize),
+}
+
+/// Addresses `start..end`, run one after the other without a jump between
+/// them, with the accumulator as it was before `start`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Stretch {
+    start: usize,
+    end: usize,
+    acc: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Compiled {
+    /// The basic blocks, in order
+    blocks: Vec<Block>,
+
+    /// The index in `blocks` of the block each address is in
+    block_of: Vec<usize>,
+
+    /// For each address and the end, the total and total size of the `acc`s
+    /// before it
+    totals: Vec<(i64, i64)>,
+}
+
+impl Compiled {
+    /// Compile `program`, or return `None` if it uses more than the puzzle's
+    /// instructions
+    pub(crate) fn new(program: &[Instruction]) -> Option<Self> {
+        if InstructionSet::required_by(program) > InstructionSet::V1 {
+            return None;
+        }
+
+        let len = program.len();
+
+        // Where blocks start. Blocks also stop at the end, so nothing runs
+        // off it.
+        let mut leaders = vec![false; len + 1];
+        leaders[len] = true;
+
+        for (address, &ins) in program.iter().enumerate() {
+            if let Instruction::Jmp(x) = ins {
+                leaders[address + 1] = true;
+
+                let target = resolve(address, x);
+                if target < len {
+                    leaders[target] = true;
+                }
+            }
+        }
+
+        let mut totals = Vec::with_capacity(len + 1);
+        let mut total = (0, 0);
+        totals.push(total);
+
+        for &ins in program {
+            if let Instruction::Acc(x) = ins {
+                total.0 += x as i64;
+                total.1 += (x as i64).abs();
+            }
+
+            totals.push(total);
+        }
+
+        let mut blocks: Vec<Block> = vec![];
+        let mut block_of = Vec::with_capacity(len);
+        let mut start = 0;
+
+        for (address, &ins) in program.iter().enumerate() {
+            block_of.push(blocks.len());
+
+            let end = address + 1;
+            if !leaders[end] {
+                continue;
+            }
+
+            blocks.push(Block {
+                start,
+                end,
+                acc: totals[end].0 - totals[start].0,
+                magnitude: totals[end].1 - totals[start].1,
+                next: match ins {
+                    Instruction::Jmp(x) => resolve(address, x),
+                    _ => end,
+                },
+                next_block: 0,
+            });
+
+            start = end;
+        }
+
+        // Every address a block goes to starts a block
+        for block in blocks.iter_mut() {
+            if block.next < len {
+                block.next_block = block_of[block.next];
+            }
+        }
+
+        Some(Self {
+            blocks,
+            block_of,
+            totals,
+        })
+    }
+
+    /// The total and total size of the unpatched `acc`s in `start..end`
+    fn totals(&self, start: usize, end: usize) -> (i64, i64) {
+        let (before, after) = (self.totals[start], self.totals[end]);
+        (after.0 - before.0, after.1 - before.1)
+    }
+
+    /// Run from address 0 with nothing visited, marking what's executed in
+    /// `visited`, adding to `acc` and appending to `path`. `patches` replace
+    /// the compiled instructions, and must only be the puzzle's instructions.
+    /// Jumps past the end terminate if `past_end` is set, and are bad jumps
+    /// otherwise.
+    pub(crate) fn run(
+        &self,
+        patches: &[(usize, Instruction)],
+        acc: &mut i32,
+        visited: &mut BitSet,
+        path: &mut Vec<Stretch>,
+        past_end: bool,
+    ) -> Exit {
+        let len = self.block_of.len();
+        let mut pc = 0;
+
+        // The block `pc` is in
+        let mut index = 0;
+
+        loop {
+            if pc >= len {
+                return Exit::Terminated(pc);
+            }
+
+            if let Some(&(_, ins)) = patches.iter().find(|&&(a, _)| a == pc) {
+                if !visited.insert(pc) {
+                    return Exit::Looped(pc);
+                }
+
+                path.push(Stretch {
+                    start: pc,
+                    end: pc + 1,
+                    acc: *acc,
+                });
+
+                let offset = match ins {
+                    Instruction::Acc(x) => match acc.checked_add(x) {
+                        Some(sum) => {
+                            *acc = sum;
+                            1
+                        }
+                        None => return Exit::MightOverflow(pc),
+                    },
+                    Instruction::Jmp(x) => x,
+                    Instruction::Nop(_) => 1,
+                    ins => unreachable!("`{}` can't be compiled", ins),
+                };
+
+                let target = resolve(pc, offset);
+                if target == BAD_JUMP || (target > len && !past_end) {
+                    return Exit::BadJump(pc);
+                }
+
+                pc = target;
+                if pc < len {
+                    index = self.block_of[pc];
+                }
+
+                continue;
+            }
+
+            let block = self.blocks[index];
+
+            // Stop short of the next patched address, and of anything that's
+            // already been executed
+            let end = patches
+                .iter()
+                .map(|&(a, _)| a)
+                .filter(|&a| a > pc && a < block.end)
+                .min()
+                .unwrap_or(block.end);
+            let whole = pc == block.start && end == block.end;
+
+            // Checked as far as `end`, which is sometimes further than what's
+            // run, so that visiting and finding a repeat is a single pass
+            let magnitude = if whole {
+                block.magnitude
+            } else {
+                self.totals(pc, end).1
+            };
+
+            if (*acc as i64).abs() + magnitude > i32::MAX as i64 {
+                return Exit::MightOverflow(pc);
+            }
+
+            let repeat = visited.insert_until_repeat(pc, end);
+            let stop = repeat.unwrap_or(end);
+
+            if stop > pc {
+                path.push(Stretch {
+                    start: pc,
+                    end: stop,
+                    acc: *acc,
+                });
+            }
+
+            let added = if whole && repeat.is_none() {
+                block.acc
+            } else {
+                self.totals(pc, stop).0
+            };
+            *acc = (*acc as i64 + added) as i32;
+
+            if let Some(address) = repeat {
+                return Exit::Looped(address);
+            }
+
+            if stop < block.end {
+                pc = stop;
+                continue;
+            }
+
+            if block.next == BAD_JUMP || (block.next > len && !past_end) {
+                return Exit::BadJump(block.end - 1);
+            }
+
+            pc = block.next;
+            index = block.next_block;
+        }
+    }
+
+    /// The cycle at `entry` of a run which looped there, given the `path`
+    /// it took and the accumulator it ended with
+    pub(crate) fn cycle(&self, path: &[Stretch], entry: usize, acc: i32) -> Cycle {
+        let i = path
+            .iter()
+            .position(|s| s.start <= entry && entry < s.end)
+            .expect("the loop's entry was executed");
+        let at = path[i];
+
+        // A stretch is either one patched instruction, which can only be
+        // entered at its start, or has nothing patched in it
+        let acc_before = at.acc as i64 + self.totals(at.start, entry).0;
+
+        // Sized up front, so each stretch is a single copy
+        let len = |path: &[Stretch]| path.iter().map(|s| s.end - s.start).sum::<usize>();
+        let mut prefix = Vec::with_capacity(len(&path[..i]) + entry - at.start);
+        let mut body = Vec::with_capacity(at.end - entry + len(&path[i + 1..]));
+
+        for s in path[..i].iter() {
+            prefix.extend(s.start..s.end);
+        }
+        prefix.extend(at.start..entry);
+
+        body.extend(entry..at.end);
+        for s in path[i + 1..].iter() {
+            body.extend(s.start..s.end);
+        }
+
+        Cycle {
+            entry,
+            body,
+            acc_delta: acc as i64 - acc_before,
+            prefix,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::example;
+    use crate::Instruction::*;
+
+    fn run(program: &[Instruction], patches: &[(usize, Instruction)]) -> (Exit, i32, Vec<usize>) {
+        let compiled = Compiled::new(program).unwrap();
+        let mut acc = 0;
+        let mut visited = BitSet::new(program.len());
+
+        let exit = compiled.run(patches, &mut acc, &mut visited, &mut vec![], false);
+        (exit, acc, visited.iter().collect())
+    }
+
+    #[test]
+    fn test_blocks() {
+        let compiled = Compiled::new(&example()).unwrap();
+
+        // 1 is the target of `jmp -3`, and so on
+        let bounds: Vec<_> = compiled.blocks.iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(bounds, vec![(0, 1), (1, 3), (3, 5), (5, 6), (6, 8), (8, 9)]);
+        assert_eq!(compiled.block_of, vec![0, 1, 1, 2, 2, 3, 4, 4, 5]);
+
+        assert_eq!(
+            compiled.blocks[3],
+            Block {
+                start: 5,
+                end: 6,
+                acc: -99,
+                magnitude: 99,
+                next: 6,
+                next_block: 4
+            }
+        );
+        assert_eq!(
+            compiled.blocks[4],
+            Block {
+                start: 6,
+                end: 8,
+                acc: 1,
+                magnitude: 1,
+                next: 3,
+                next_block: 2
+            }
+        );
+        assert_eq!(compiled.totals(5, 8), (-98, 100));
+    }
+
+    #[test]
+    fn test_run() {
+        assert_eq!(
+            run(&example(), &[]),
+            (Exit::Looped(1), 5, vec![0, 1, 2, 3, 4, 6, 7])
+        );
+    }
+
+    #[test]
+    fn test_cycle() {
+        let cycle = |program: &[Instruction], patches: &[(usize, Instruction)]| {
+            let compiled = Compiled::new(program).unwrap();
+            let mut acc = 0;
+            let mut path = vec![];
+            let visited = &mut BitSet::new(program.len());
+
+            match compiled.run(patches, &mut acc, visited, &mut path, false) {
+                Exit::Looped(entry) => compiled.cycle(&path, entry, acc),
+                exit => panic!("unexpected exit {:?}", exit),
+            }
+        };
+
+        assert_eq!(
+            cycle(&example(), &[]),
+            Cycle {
+                entry: 1,
+                body: vec![1, 2, 6, 7, 3, 4],
+                acc_delta: 5,
+                prefix: vec![0]
+            }
+        );
+
+        // Entering the loop partway through a block, and through a patch
+        assert_eq!(
+            cycle(&[Acc(1), Acc(2), Acc(4), Nop(-2)], &[(3, Jmp(-2))]),
+            Cycle {
+                entry: 1,
+                body: vec![1, 2, 3],
+                acc_delta: 6,
+                prefix: vec![0]
+            }
+        );
+        assert_eq!(
+            cycle(&[Acc(1), Nop(0), Acc(2), Jmp(-2)], &[(1, Acc(4))]),
+            Cycle {
+                entry: 1,
+                body: vec![1, 2, 3],
+                acc_delta: 6,
+                prefix: vec![0]
+            }
+        );
+    }
+
+    #[test]
+    fn test_run_patched() {
+        assert_eq!(
+            run(&example(), &[(7, Nop(-4))]),
+            (Exit::Terminated(9), 8, vec![0, 1, 2, 6, 7, 8])
+        );
+
+        // A patched jump into the middle of a block that's partly run
+        assert_eq!(
+            run(&[Acc(1), Acc(2), Nop(-1)], &[(2, Jmp(-1))]),
+            (Exit::Looped(1), 3, vec![0, 1, 2])
+        );
+        assert_eq!(
+            run(&[Acc(1), Acc(2), Nop(0)], &[(2, Jmp(-5))]),
+            (Exit::BadJump(2), 3, vec![0, 1, 2])
+        );
+    }
+
+    #[test]
+    fn test_overflowing_jumps() {
+        assert_eq!(
+            run(&[Acc(1), Jmp(isize::MAX)], &[]),
+            (Exit::BadJump(1), 1, vec![0, 1])
+        );
+        assert_eq!(
+            run(&[Nop(0), Acc(1)], &[(1, Jmp(isize::MIN))]),
+            (Exit::BadJump(1), 0, vec![0, 1])
+        );
+    }
+
+    #[test]
+    fn test_overflow_margin() {
+        assert_eq!(
+            run(&[Acc(i32::MAX), Acc(-5)], &[]),
+            (Exit::MightOverflow(0), 0, vec![])
+        );
+    }
+}
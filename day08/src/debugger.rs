@@ -0,0 +1,483 @@
+//! Command interpreter behind the `debugger` binary.
+//!
+//! Each command is one line of text, and produces some text to show the user.
+//! Run `help` for the list of commands.
+
+use std::fmt::Write;
+use std::fs;
+
+use crate::asm::assemble_with;
+use crate::{Instruction, InstructionSet, Reg, StepError, Vm, NUM_REGISTERS, STEP_BUDGET};
+
+const HELP: &str = "\
+load FILE             load a program from an assembly file
+break ADDR            stop before executing ADDR
+break acc OP N        stop when the accumulator starts to satisfy OP N (== != < <= > >=)
+breaks                list breakpoints
+delete N              remove breakpoint N
+step [N]              execute N instructions (default 1)
+continue              run until a breakpoint, the end, a loop, or too many steps
+regs                  show the program counter and registers
+visited               show the addresses executed so far
+list [ADDR [N]]       show N instructions starting at ADDR (default: around pc)
+patch ADDR INS        replace the instruction at ADDR, e.g. `patch 7 nop -4`
+reset                 restart the program, keeping patches and breakpoints
+quit                  exit";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "==" => Some(Comparison::Eq),
+            "!=" => Some(Comparison::Ne),
+            "<" => Some(Comparison::Lt),
+            "<=" => Some(Comparison::Le),
+            ">" => Some(Comparison::Gt),
+            ">=" => Some(Comparison::Ge),
+            _ => None,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+
+    fn holds(self, lhs: i32, rhs: i32) -> bool {
+        match self {
+            Comparison::Eq => lhs == rhs,
+            Comparison::Ne => lhs != rhs,
+            Comparison::Lt => lhs < rhs,
+            Comparison::Le => lhs <= rhs,
+            Comparison::Gt => lhs > rhs,
+            Comparison::Ge => lhs >= rhs,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stop before executing this address
+    Address(usize),
+
+    /// Stop after an instruction makes the accumulator satisfy the comparison,
+    /// when it didn't before
+    Acc(Comparison, i32),
+}
+
+/// What the caller should do after a command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Response {
+    Output(String),
+    Quit,
+}
+
+#[derive(Clone, Debug)]
+pub struct Debugger {
+    vm: Vm<'static>,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl Debugger {
+    pub fn new(program: Vec<Instruction>) -> Self {
+        Self {
+            vm: Vm::new(program),
+            breakpoints: vec![],
+        }
+    }
+
+    pub fn vm(&self) -> &Vm<'static> {
+        &self.vm
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Run one line of input.
+    pub fn execute(&mut self, line: &str) -> Response {
+        let words: Vec<_> = line.split_whitespace().collect();
+
+        let output = match words.as_slice() {
+            [] => Ok(String::new()),
+            ["help"] | ["h"] => Ok(HELP.to_string()),
+            ["quit"] | ["q"] => return Response::Quit,
+            ["load", path] => self.load(path),
+            ["break", "acc", op, n] | ["b", "acc", op, n] => self.break_acc(op, n),
+            ["break", addr] | ["b", addr] => self.break_address(addr),
+            ["breaks"] => Ok(self.list_breakpoints()),
+            ["delete", n] | ["d", n] => self.delete(n),
+            ["step"] | ["s"] => Ok(self.step(1)),
+            ["step", n] | ["s", n] => parse_number(n).map(|n| self.step(n)),
+            ["continue"] | ["c"] => Ok(self.cont()),
+            ["regs"] | ["r"] => Ok(self.regs()),
+            ["visited"] => Ok(format!("{:?}", self.vm.visited())),
+            ["list"] | ["l"] => {
+                let start = self.vm.pc().saturating_sub(3);
+                self.list(start, 7)
+            }
+            ["list", addr] | ["l", addr] => parse_number(addr).and_then(|a| self.list(a, 7)),
+            ["list", addr, n] | ["l", addr, n] => {
+                parse_number(addr).and_then(|a| self.list(a, parse_number(n)?))
+            }
+            ["patch", addr, ins @ ..] => self.patch(addr, &ins.join(" ")),
+            ["reset"] => {
+                self.vm.reset();
+                Ok(self.regs())
+            }
+            _ => Err(format!("unknown command `{}`; try `help`", line.trim())),
+        };
+
+        match output {
+            Ok(output) => Response::Output(output),
+            Err(e) => Response::Output(format!("error: {}", e)),
+        }
+    }
+
+    fn load(&mut self, path: &str) -> Result<String, String> {
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let program = assemble_with(&source, InstructionSet::LATEST).map_err(|e| e.to_string())?;
+        let len = program.len();
+
+        self.vm = Vm::new(program);
+        self.breakpoints.clear();
+
+        Ok(format!("loaded {} instructions", len))
+    }
+
+    fn break_address(&mut self, addr: &str) -> Result<String, String> {
+        let addr = parse_number(addr)?;
+        self.breakpoints.push(Breakpoint::Address(addr));
+        Ok(format!(
+            "breakpoint {} at {:04}",
+            self.breakpoints.len() - 1,
+            addr
+        ))
+    }
+
+    fn break_acc(&mut self, op: &str, n: &str) -> Result<String, String> {
+        let op = Comparison::parse(op).ok_or(format!("unknown comparison `{}`", op))?;
+        let n = n.parse().map_err(|_| format!("invalid number `{}`", n))?;
+
+        self.breakpoints.push(Breakpoint::Acc(op, n));
+        Ok(format!(
+            "breakpoint {} when acc {} {}",
+            self.breakpoints.len() - 1,
+            op.symbol(),
+            n
+        ))
+    }
+
+    fn list_breakpoints(&self) -> String {
+        let mut out = String::new();
+
+        for (i, bp) in self.breakpoints.iter().enumerate() {
+            match bp {
+                Breakpoint::Address(a) => writeln!(out, "{}: at {:04}", i, a).unwrap(),
+                Breakpoint::Acc(op, n) => {
+                    writeln!(out, "{}: when acc {} {}", i, op.symbol(), n).unwrap()
+                }
+            }
+        }
+
+        out.pop();
+        out
+    }
+
+    fn delete(&mut self, n: &str) -> Result<String, String> {
+        let n = parse_number(n)?;
+
+        if n >= self.breakpoints.len() {
+            return Err(format!("no breakpoint {}", n));
+        }
+
+        self.breakpoints.remove(n);
+        Ok(format!("deleted breakpoint {}", n))
+    }
+
+    /// Execute one instruction, returning a message if execution stopped
+    fn step_once(&mut self) -> Option<String> {
+        match self.vm.step() {
+            Ok(()) => None,
+            Err(StepError::LastInstructionExecuted) => Some("program terminated".to_string()),
+            Err(StepError::Halted) => Some("program halted".to_string()),
+            Err(StepError::InstructionAlreadyExecuted(a)) => {
+                Some(format!("loop detected: {:04} was already executed", a))
+            }
+            Err(StepError::Fault(fault)) => Some(format!("fault: {}", fault)),
+        }
+    }
+
+    fn step(&mut self, n: usize) -> String {
+        for _ in 0..n {
+            if let Some(msg) = self.step_once() {
+                return format!("{}\n{}", msg, self.regs());
+            }
+        }
+
+        self.regs()
+    }
+
+    fn cont(&mut self) -> String {
+        for _ in 0..STEP_BUDGET {
+            let acc_before = self.vm.acc();
+
+            if let Some(msg) = self.step_once() {
+                return format!("{}\n{}", msg, self.regs());
+            }
+
+            let pc = self.vm.pc();
+            let acc = self.vm.acc();
+
+            let hit = self.breakpoints.iter().position(|&bp| match bp {
+                Breakpoint::Address(a) => a == pc,
+                Breakpoint::Acc(op, n) => op.holds(acc, n) && !op.holds(acc_before, n),
+            });
+
+            if let Some(i) = hit {
+                return format!("hit breakpoint {}\n{}", i, self.regs());
+            }
+        }
+
+        format!("still running after {} steps\n{}", STEP_BUDGET, self.regs())
+    }
+
+    fn regs(&self) -> String {
+        let pc = self.vm.pc();
+
+        let next = match self.vm.instruction(pc) {
+            _ if self.vm.is_halted() => "<halted>".to_string(),
+            Some(ins) => ins.to_string(),
+            None => "<end>".to_string(),
+        };
+
+        // The puzzle's programs only ever use the accumulator
+        let mut regs = format!("acc={}", self.vm.acc());
+        if InstructionSet::required_by(&self.vm.instructions()) > InstructionSet::V1 {
+            for reg in (1..NUM_REGISTERS).filter_map(Reg::new) {
+                write!(regs, " {}={}", reg.name(), self.vm.register(reg)).unwrap();
+            }
+        }
+
+        format!("pc={:04} {} next: {}", pc, regs, next)
+    }
+
+    fn list(&self, start: usize, n: usize) -> Result<String, String> {
+        if start >= self.vm.len() {
+            return Err(format!("no instruction at {:04}", start));
+        }
+
+        let end = start.saturating_add(n).min(self.vm.len());
+        let mut out = String::new();
+
+        for address in start..end {
+            let ins = self.vm.instruction(address).unwrap();
+            let marker = if address == self.vm.pc() { '>' } else { ' ' };
+
+            let has_break = self.breakpoints.contains(&Breakpoint::Address(address));
+            let bp = if has_break { '*' } else { ' ' };
+
+            let seen = if self.vm.has_visited(address) {
+                "  (visited)"
+            } else {
+                ""
+            };
+
+            writeln!(out, "{}{} {:04}  {}{}", marker, bp, address, ins, seen).unwrap();
+        }
+
+        out.pop();
+        Ok(out)
+    }
+
+    fn patch(&mut self, addr: &str, ins: &str) -> Result<String, String> {
+        let addr = parse_number(addr)?;
+
+        let ins = match assemble_with(ins, InstructionSet::LATEST)
+            .map_err(|e| e.to_string())?
+            .as_slice()
+        {
+            &[ins] => ins,
+            _ => return Err("expected exactly one instruction".to_string()),
+        };
+
+        let old = self
+            .vm
+            .patch(addr, ins)
+            .ok_or(format!("no instruction at {:04}", addr))?;
+
+        Ok(format!("{:04}: {} -> {}", addr, old, ins))
+    }
+}
+
+fn parse_number(s: &str) -> Result<usize, String> {
+    s.parse().map_err(|_| format!("invalid number `{}`", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::example;
+    use crate::Instruction::*;
+
+    fn debugger() -> Debugger {
+        Debugger::new(example())
+    }
+
+    fn output(debugger: &mut Debugger, line: &str) -> String {
+        match debugger.execute(line) {
+            Response::Output(s) => s,
+            Response::Quit => panic!("unexpected quit"),
+        }
+    }
+
+    #[test]
+    fn test_step_and_regs() {
+        let mut dbg = debugger();
+
+        assert_eq!(output(&mut dbg, "step 2"), "pc=0002 acc=1 next: jmp +4");
+        assert_eq!(output(&mut dbg, "s"), "pc=0006 acc=1 next: acc +1");
+        assert_eq!(output(&mut dbg, "visited"), "[0, 1, 2]");
+    }
+
+    #[test]
+    fn test_address_breakpoint() {
+        let mut dbg = debugger();
+
+        output(&mut dbg, "break 3");
+        assert_eq!(
+            output(&mut dbg, "continue"),
+            "hit breakpoint 0\npc=0003 acc=2 next: acc +3"
+        );
+        assert_eq!(
+            output(&mut dbg, "c"),
+            "loop detected: 0001 was already executed\npc=0001 acc=5 next: acc +1"
+        );
+    }
+
+    #[test]
+    fn test_acc_breakpoint() {
+        let mut dbg = debugger();
+
+        output(&mut dbg, "break acc >= 5");
+        assert_eq!(
+            output(&mut dbg, "c"),
+            "hit breakpoint 0\npc=0004 acc=5 next: jmp -3"
+        );
+    }
+
+    #[test]
+    fn test_acc_breakpoint_fires_on_change() {
+        let mut dbg = Debugger::new(vec![Acc(5), Acc(1), Acc(-6), Acc(5), Acc(0)]);
+
+        output(&mut dbg, "break acc >= 5");
+        assert_eq!(
+            output(&mut dbg, "c"),
+            "hit breakpoint 0\npc=0001 acc=5 next: acc +1"
+        );
+
+        // Still true after `acc +1`, so it carries on until it becomes true again
+        assert_eq!(
+            output(&mut dbg, "c"),
+            "hit breakpoint 0\npc=0004 acc=5 next: acc +0"
+        );
+        assert_eq!(
+            output(&mut dbg, "c"),
+            "program terminated\npc=0005 acc=5 next: <end>"
+        );
+    }
+
+    #[test]
+    fn test_patch_and_reset() {
+        let mut dbg = debugger();
+
+        assert_eq!(output(&mut dbg, "patch 7 nop -4"), "0007: jmp -4 -> nop -4");
+        assert_eq!(
+            output(&mut dbg, "c"),
+            "program terminated\npc=0009 acc=8 next: <end>"
+        );
+
+        // Patches survive a reset
+        output(&mut dbg, "reset");
+        output(&mut dbg, "c");
+        assert_eq!(dbg.vm().acc(), 8);
+    }
+
+    #[test]
+    fn test_halt_shows_registers() {
+        let mut dbg = Debugger::new(vec![Set(Reg::B, 2), Add(Reg::A, 1), Hlt, Acc(5)]);
+
+        assert_eq!(
+            output(&mut dbg, "c"),
+            "program halted\npc=0002 acc=1 b=2 c=0 d=0 next: <halted>"
+        );
+    }
+
+    #[test]
+    fn test_continue_gives_up() {
+        let mut dbg = Debugger::new(vec![Add(Reg::B, 1), Jnz(Reg::B, -1)]);
+
+        assert_eq!(
+            output(&mut dbg, "c"),
+            "still running after 1000000 steps\npc=0000 acc=0 b=500000 c=0 d=0 next: add b +1"
+        );
+    }
+
+    #[test]
+    fn test_fault() {
+        let mut dbg = Debugger::new(vec![Acc(1), Jmp(-4)]);
+
+        assert_eq!(
+            output(&mut dbg, "c"),
+            "fault: jump by -4 at 0001 is before the start\npc=0001 acc=1 next: jmp -4"
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        let mut dbg = debugger();
+        output(&mut dbg, "break 2");
+        output(&mut dbg, "s");
+
+        assert_eq!(
+            output(&mut dbg, "list 0 3"),
+            "   0000  nop +0  (visited)\n>  0001  acc +1\n * 0002  jmp +4"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut dbg = debugger();
+
+        assert_eq!(
+            output(&mut dbg, "frobnicate"),
+            "error: unknown command `frobnicate`; try `help`"
+        );
+        assert_eq!(
+            output(&mut dbg, "patch 20 nop +0"),
+            "error: no instruction at 0020"
+        );
+        assert_eq!(output(&mut dbg, "delete 0"), "error: no breakpoint 0");
+        assert_eq!(output(&mut dbg, "list 9"), "error: no instruction at 0009");
+        assert_eq!(
+            output(&mut dbg, "list 3 18446744073709551615"),
+            output(&mut dbg, "list 3 6")
+        );
+        assert_eq!(dbg.execute("quit"), Response::Quit);
+    }
+}
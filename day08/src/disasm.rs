@@ -0,0 +1,199 @@
+//! Turning instructions back into assembly.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::Instruction;
+
+/// The address a jump at `address` by `offset` lands on, if it's not negative
+/// and the sum doesn't overflow.
+fn jump_target(address: usize, offset: isize) -> Option<usize> {
+    match (address as isize).checked_add(offset) {
+        Some(target) if !target.is_negative() => Some(target as usize),
+        _ => None,
+    }
+}
+
+/// Labels for every address a jump lands on, named `L0`, `L1`, ... in
+/// address order. A jump just past the last instruction is labelled `end`.
+pub fn jump_labels(program: &[Instruction]) -> BTreeMap<usize, String> {
+    let mut targets: Vec<_> = program
+        .iter()
+        .enumerate()
+        .filter_map(|(address, &ins)| jump_target(address, ins.jump_offset()?))
+        .filter(|&target| target <= program.len())
+        .collect();
+
+    targets.sort_unstable();
+    targets.dedup();
+
+    let mut next_label = 0;
+
+    targets
+        .into_iter()
+        .map(|target| {
+            let label = if target == program.len() {
+                "end".to_string()
+            } else {
+                next_label += 1;
+                format!("L{}", next_label - 1)
+            };
+
+            (target, label)
+        })
+        .collect()
+}
+
+/// A jump written with a label in place of its offset
+fn with_label(ins: Instruction, label: &str) -> String {
+    match ins {
+        Instruction::Jz(r, _) => format!("jz {} {}", r.name(), label),
+        Instruction::Jnz(r, _) => format!("jnz {} {}", r.name(), label),
+        _ => format!("jmp {}", label),
+    }
+}
+
+/// A listing of the program, with a label before every jump destination and
+/// each instruction's address (and absolute jump target) in a comment.
+///
+/// The listing is valid assembly, so `asm::assemble` gives back the same program.
+pub fn listing(program: &[Instruction]) -> String {
+    let labels = jump_labels(program);
+    let mut out = String::new();
+
+    for (address, &ins) in program.iter().enumerate() {
+        if let Some(label) = labels.get(&address) {
+            writeln!(out, "{}:", label).unwrap();
+        }
+
+        let (text, target) = match ins.jump_offset() {
+            Some(x) => match jump_target(address, x) {
+                Some(target) => match labels.get(&target) {
+                    Some(label) => (with_label(ins, label), format!(" -> {:04}", target)),
+                    None => (ins.to_string(), format!(" -> {:04} (out of range)", target)),
+                },
+                None if x < 0 => (ins.to_string(), " -> negative (out of range)".to_string()),
+                // Too far to even write down
+                None => (ins.to_string(), String::new()),
+            },
+            None => (ins.to_string(), String::new()),
+        };
+
+        writeln!(out, "    {:<16}; {:04}{}", text, address, target).unwrap();
+    }
+
+    if let Some(label) = labels.get(&program.len()) {
+        writeln!(out, "{}:", label).unwrap();
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::{assemble, assemble_with};
+    use crate::Instruction::*;
+    use crate::{InstructionSet, Reg};
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Acc(3).to_string(), "acc +3");
+        assert_eq!(Jmp(-4).to_string(), "jmp -4");
+        assert_eq!(Nop(0).to_string(), "nop +0");
+        assert_eq!(Mul(Reg::B, 3).to_string(), "mul b +3");
+        assert_eq!(Jnz(Reg::D, -2).to_string(), "jnz d -2");
+        assert_eq!(Out(Reg::A).to_string(), "out a");
+        assert_eq!(Hlt.to_string(), "hlt");
+    }
+
+    #[test]
+    fn test_listing() {
+        let program = [Nop(0), Acc(1), Jmp(4), Acc(3), Jmp(-3), Acc(-99), Jmp(1)];
+
+        assert_eq!(
+            listing(&program),
+            "    nop +0          ; 0000
+L0:
+    acc +1          ; 0001
+    jmp L1          ; 0002 -> 0006
+    acc +3          ; 0003
+    jmp L0          ; 0004 -> 0001
+    acc -99         ; 0005
+L1:
+    jmp end         ; 0006 -> 0007
+end:
+"
+        );
+    }
+
+    #[test]
+    fn test_listing_out_of_range() {
+        let program = [Jmp(-2), Jmp(5)];
+
+        assert_eq!(
+            listing(&program),
+            "    jmp -2          ; 0000 -> negative (out of range)
+    jmp +5          ; 0001 -> 0006 (out of range)
+"
+        );
+    }
+
+    #[test]
+    fn test_listing_overflowing_jump() {
+        let program = [Nop(0), Jmp(isize::MAX), Jmp(isize::MIN)];
+
+        assert_eq!(
+            listing(&program),
+            format!(
+                "    nop +0          ; 0000
+    jmp +{}; 0001
+    jmp {}; 0002 -> negative (out of range)
+",
+                isize::MAX,
+                isize::MIN
+            )
+        );
+    }
+
+    #[test]
+    fn test_listing_round_trip() {
+        let program = vec![
+            Nop(0),
+            Acc(1),
+            Jmp(4),
+            Acc(3),
+            Jmp(-3),
+            Acc(-99),
+            Acc(1),
+            Jmp(-4),
+            Acc(6),
+            Jmp(1),
+            Jmp(-20),
+        ];
+
+        assert_eq!(assemble(&listing(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn test_listing_v2_round_trip() {
+        let a = Reg::A;
+        let program = vec![
+            Set(Reg::B, 3),
+            Add(a, 2),
+            Add(Reg::B, -1),
+            Jnz(Reg::B, -2),
+            Jz(a, 2),
+            Out(a),
+            Hlt,
+        ];
+
+        let listing = listing(&program);
+
+        assert!(listing.contains("    jnz b L0        ; 0003 -> 0001\n"));
+        assert_eq!(
+            assemble_with(&listing, InstructionSet::V2).unwrap(),
+            program
+        );
+    }
+}
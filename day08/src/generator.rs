@@ -0,0 +1,320 @@
+//! Random `acc`/`jmp`/`nop` programs, for fuzzing the VM and the analyses
+//! against each other.
+
+use std::ops::RangeInclusive;
+
+use crate::Instruction;
+
+/// What the generated programs are guaranteed to do.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shape {
+    /// No guarantees: programs may terminate, loop, or jump out of range
+    Any,
+
+    /// Every jump goes forwards and lands inside the program or just past
+    /// its end, so programs always terminate
+    Terminating,
+
+    /// Every jump lands inside the program and the last instruction is a
+    /// `jmp`, so programs never terminate (or fault)
+    Looping,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratorConfig {
+    pub seed: u64,
+
+    /// How many instructions each program has; must not be empty or include
+    /// zero
+    pub len: RangeInclusive<usize>,
+
+    /// The largest distance a `jmp` or `nop` argument can be
+    pub max_jump: usize,
+
+    /// The largest amount an `acc` can add or subtract. For the `Terminating`
+    /// and `Looping` shapes, this times the longest length must fit in an
+    /// `i32`, so the accumulator can't overflow.
+    pub max_acc: i32,
+
+    /// Out of every 100 instructions, roughly how many are `jmp`s and `nop`s.
+    /// The rest are `acc`s.
+    pub jmp_percent: u32,
+    pub nop_percent: u32,
+
+    pub shape: Shape,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            len: 1..=50,
+            max_jump: 20,
+            max_acc: 100,
+            jmp_percent: 30,
+            nop_percent: 20,
+            shape: Shape::Any,
+        }
+    }
+}
+
+/// An endless, deterministic stream of random programs.
+#[derive(Clone, Debug)]
+pub struct ProgramGenerator {
+    config: GeneratorConfig,
+    rng: Rng,
+}
+
+impl ProgramGenerator {
+    /// # Panics
+    ///
+    /// If `config.len` is empty or includes zero, `config.max_acc` is
+    /// negative, or `config.max_acc` is too big for the shape.
+    pub fn new(config: GeneratorConfig) -> Self {
+        let (min, max) = (*config.len.start(), *config.len.end());
+        assert!(
+            min >= 1 && min <= max,
+            "invalid length range {:?}",
+            config.len
+        );
+        assert!(config.max_acc >= 0, "negative max_acc {}", config.max_acc);
+
+        // Each instruction runs at most once before the program terminates
+        // or repeats itself, so this bounds the accumulator
+        if config.shape != Shape::Any {
+            let total = (config.max_acc as u128).checked_mul(max as u128);
+            assert!(
+                total.is_some_and(|total| total <= i32::MAX as u128),
+                "max_acc {} can overflow the accumulator in {} instructions",
+                config.max_acc,
+                max
+            );
+        }
+
+        let rng = Rng::new(config.seed);
+        Self { config, rng }
+    }
+
+    pub fn next_program(&mut self) -> Vec<Instruction> {
+        let (min, max) = (*self.config.len.start(), *self.config.len.end());
+        let len = min + self.rng.below((max - min + 1) as u64) as usize;
+
+        let mut program: Vec<_> = (0..len)
+            .map(|address| self.instruction(address, len))
+            .collect();
+
+        if self.config.shape == Shape::Looping {
+            program[len - 1] = Instruction::Jmp(self.offset(len - 1, len));
+        }
+
+        program
+    }
+
+    fn instruction(&mut self, address: usize, len: usize) -> Instruction {
+        let roll = self.rng.below(100) as u32;
+
+        if roll < self.config.jmp_percent {
+            Instruction::Jmp(self.offset(address, len))
+        } else if roll < self.config.jmp_percent + self.config.nop_percent {
+            // A `nop`'s argument only matters once it's flipped into a `jmp`
+            Instruction::Nop(self.offset(address, len))
+        } else {
+            let max = self.config.max_acc as i64;
+            Instruction::Acc((self.rng.below(2 * max as u64 + 1) as i64 - max) as i32)
+        }
+    }
+
+    /// A jump offset from `address` fitting the shape
+    fn offset(&mut self, address: usize, len: usize) -> isize {
+        let max = self.config.max_jump as isize;
+        let address = address as isize;
+        let len = len as isize;
+
+        let (lo, hi) = match self.config.shape {
+            Shape::Any => (-max, max),
+            Shape::Terminating => (1, max.min(len - address).max(1)),
+            Shape::Looping => ((-max).max(-address), max.min(len - 1 - address)),
+        };
+
+        lo + self.rng.below((hi - lo + 1) as u64) as isize
+    }
+}
+
+impl Iterator for ProgramGenerator {
+    type Item = Vec<Instruction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_program())
+    }
+}
+
+/// splitmix64, which is plenty for generating test programs
+#[derive(Clone, Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::{find_repair, Cfg};
+    use crate::reference::{self, Ending};
+    use crate::repair::{repair, Mutation};
+    use crate::{Fault, Outcome, Vm};
+
+    fn generate(shape: Shape, seed: u64, count: usize) -> Vec<Vec<Instruction>> {
+        let config = GeneratorConfig {
+            seed,
+            shape,
+            ..Default::default()
+        };
+
+        ProgramGenerator::new(config).take(count).collect()
+    }
+
+    /// What the reference interpreter would say about a `Vm` run
+    fn ending(outcome: &Outcome) -> Ending {
+        match *outcome {
+            Outcome::Terminated => Ending::Terminated,
+            Outcome::Looped(ref cycle) => Ending::Looped { entry: cycle.entry },
+            Outcome::Faulted(Fault::NegativeJump { address, .. }) => {
+                Ending::NegativeJump { address }
+            }
+            Outcome::Faulted(Fault::JumpOutOfRange { address, .. }) => {
+                Ending::JumpOutOfRange { address }
+            }
+            Outcome::Faulted(Fault::Overflow { address, .. }) => Ending::Overflow { address },
+            ref other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_programs() {
+        assert_eq!(generate(Shape::Any, 3, 20), generate(Shape::Any, 3, 20));
+        assert_ne!(generate(Shape::Any, 3, 20), generate(Shape::Any, 4, 20));
+    }
+
+    #[test]
+    fn test_config_respected() {
+        let config = GeneratorConfig {
+            len: 5..=8,
+            max_jump: 3,
+            max_acc: 10,
+            ..Default::default()
+        };
+
+        for program in ProgramGenerator::new(config).take(200) {
+            assert!((5..=8).contains(&program.len()));
+
+            for ins in program {
+                match ins {
+                    Instruction::Acc(x) => assert!(x.abs() <= 10),
+                    Instruction::Jmp(x) | Instruction::Nop(x) => assert!(x.abs() <= 3),
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_big_accs_dont_overflow() {
+        let config = GeneratorConfig {
+            len: 4..=4,
+            max_acc: i32::MAX / 4,
+            jmp_percent: 0,
+            nop_percent: 0,
+            shape: Shape::Terminating,
+            ..Default::default()
+        };
+
+        for program in ProgramGenerator::new(config).take(50) {
+            assert!(Vm::borrowed(&program).run().terminated(), "{:?}", program);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid length range")]
+    fn test_zero_length() {
+        ProgramGenerator::new(GeneratorConfig {
+            len: 0..=0,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "can overflow the accumulator")]
+    fn test_max_acc_too_big() {
+        ProgramGenerator::new(GeneratorConfig {
+            len: 1..=5,
+            max_acc: i32::MAX / 4,
+            shape: Shape::Looping,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_shapes() {
+        for program in generate(Shape::Terminating, 1, 300) {
+            assert_eq!(reference::run(&program).0, Ending::Terminated);
+        }
+
+        for program in generate(Shape::Looping, 2, 300) {
+            assert!(matches!(reference::run(&program).0, Ending::Looped { .. }));
+        }
+    }
+
+    #[test]
+    fn test_vm_matches_reference_and_cfg() {
+        let shapes = [Shape::Any, Shape::Terminating, Shape::Looping];
+
+        for (seed, &shape) in shapes.iter().enumerate() {
+            for program in generate(shape, seed as u64, 500) {
+                let mut vm = Vm::borrowed(&program);
+                let outcome = vm.run();
+                let expected = reference::run(&program);
+
+                assert_eq!((ending(&outcome), vm.acc()), expected, "{:?}", program);
+                assert_eq!(
+                    Cfg::new(&program).terminating()[0],
+                    outcome.terminated(),
+                    "{:?}",
+                    program
+                );
+
+                // The same VM should give the same answer again after a reset
+                vm.reset();
+                assert_eq!(vm.run(), outcome);
+
+                // The linear-time repair is one of those found by brute force
+                if !outcome.terminated() {
+                    let flips: Vec<_> = repair(&program, &[Mutation::Flip], 1)
+                        .iter()
+                        .map(|r| r.edits[0].address())
+                        .collect();
+
+                    match find_repair(&program) {
+                        Some(address) => assert!(flips.contains(&address), "{:?}", program),
+                        None => assert!(flips.is_empty(), "{:?}", program),
+                    }
+                }
+            }
+        }
+    }
+}
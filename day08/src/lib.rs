@@ -1,6 +1,116 @@
+pub mod accum;
+pub mod asm;
+mod bitset;
+pub mod bytecode;
+pub mod cfg;
+mod compiled;
+pub mod debugger;
+pub mod disasm;
+pub mod generator;
+pub mod reference;
+pub mod repair;
+pub mod trace;
+
+use std::borrow::Cow;
+use std::cell::OnceCell;
 use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use bitset::BitSet;
+use compiled::{Compiled, Exit, Stretch};
+use trace::TraceEntry;
+
+/// Number of registers in the machine
+pub const NUM_REGISTERS: usize = 4;
+
+/// How many steps a program gets before it's taken not to terminate, where
+/// it's run without a person watching (`repair`, `trace::record` and the
+/// debugger's `continue`). Conditional jumps make loops that revisit an
+/// address with different registers, which can take billions of steps.
+pub const STEP_BUDGET: u64 = 1_000_000;
+
+/// A register, named `a` to `d` in assembly. Register `a` is the accumulator.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Reg(u8);
+
+impl Reg {
+    pub const A: Reg = Reg(0);
+    pub const B: Reg = Reg(1);
+    pub const C: Reg = Reg(2);
+    pub const D: Reg = Reg(3);
+
+    /// The accumulator, which `acc` adds to
+    pub const ACC: Reg = Reg::A;
+
+    pub fn new(index: usize) -> Option<Self> {
+        if index < NUM_REGISTERS {
+            Some(Reg(index as u8))
+        } else {
+            None
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn name(self) -> char {
+        (b'a' + self.0) as char
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.as_bytes() {
+            &[c] if c.is_ascii_lowercase() => Reg::new((c - b'a') as usize),
+            _ => None,
+        }
+    }
+}
+
+/// Which instructions a program may use.
+///
+/// Versions only ever add instructions, so anything valid in one version is
+/// valid in every later one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum InstructionSet {
+    /// The puzzle's boot code: `acc`, `jmp` and `nop`
+    V1 = 1,
+
+    /// Adds registers and `add`, `mul`, `set`, `jz`, `jnz`, `out` and `hlt`
+    V2 = 2,
+}
+
+impl InstructionSet {
+    pub const LATEST: InstructionSet = InstructionSet::V2;
+
+    pub fn from_version(version: u16) -> Option<Self> {
+        match version {
+            1 => Some(InstructionSet::V1),
+            2 => Some(InstructionSet::V2),
+            _ => None,
+        }
+    }
+
+    pub fn version(self) -> u16 {
+        self as u16
+    }
+
+    pub fn supports(self, instruction: Instruction) -> bool {
+        instruction.instruction_set() <= self
+    }
+
+    /// The oldest instruction set that can run every instruction in `program`
+    pub fn required_by(program: &[Instruction]) -> Self {
+        program
+            .iter()
+            .map(|ins| ins.instruction_set())
+            .max()
+            .unwrap_or(InstructionSet::V1)
+    }
+}
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Instruction {
     /// Increment or decrement global `accumulator` by an amount
     Acc(i32),
@@ -10,144 +120,876 @@ pub enum Instruction {
 
     /// No-op
     Nop(isize),
+
+    /// Add an amount to a register (V2)
+    Add(Reg, i32),
+
+    /// Multiply a register by an amount (V2)
+    Mul(Reg, i32),
+
+    /// Set a register to an amount (V2)
+    Set(Reg, i32),
+
+    /// Jump relative to current instruction if a register is zero (V2)
+    Jz(Reg, isize),
+
+    /// Jump relative to current instruction if a register is not zero (V2)
+    Jnz(Reg, isize),
+
+    /// Write a register to the output (V2)
+    Out(Reg),
+
+    /// Stop the program; this counts as terminating (V2)
+    Hlt,
+}
+
+impl Instruction {
+    /// The instruction with `jmp` and `nop` swapped, or `None` for anything else
+    pub fn flipped(self) -> Option<Self> {
+        match self {
+            Instruction::Jmp(x) => Some(Instruction::Nop(x)),
+            Instruction::Nop(x) => Some(Instruction::Jmp(x)),
+            _ => None,
+        }
+    }
+
+    /// The first instruction set this instruction is part of
+    pub fn instruction_set(self) -> InstructionSet {
+        match self {
+            Instruction::Acc(_) | Instruction::Jmp(_) | Instruction::Nop(_) => InstructionSet::V1,
+            _ => InstructionSet::V2,
+        }
+    }
+
+    /// Whether where this instruction goes depends on a register
+    pub fn is_conditional(self) -> bool {
+        matches!(self, Instruction::Jz(..) | Instruction::Jnz(..))
+    }
+
+    /// The relative offset of an instruction that may jump
+    pub fn jump_offset(self) -> Option<isize> {
+        match self {
+            Instruction::Jmp(x) | Instruction::Jz(_, x) | Instruction::Jnz(_, x) => Some(x),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+/// Formats the instruction as assembly, e.g. `jmp -3`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Acc(x) => write!(f, "acc {:+}", x),
+            Instruction::Jmp(x) => write!(f, "jmp {:+}", x),
+            Instruction::Nop(x) => write!(f, "nop {:+}", x),
+            Instruction::Add(r, x) => write!(f, "add {} {:+}", r.name(), x),
+            Instruction::Mul(r, x) => write!(f, "mul {} {:+}", r.name(), x),
+            Instruction::Set(r, x) => write!(f, "set {} {:+}", r.name(), x),
+            Instruction::Jz(r, x) => write!(f, "jz {} {:+}", r.name(), x),
+            Instruction::Jnz(r, x) => write!(f, "jnz {} {:+}", r.name(), x),
+            Instruction::Out(r) => write!(f, "out {}", r.name()),
+            Instruction::Hlt => write!(f, "hlt"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum StepError {
     InstructionAlreadyExecuted(usize),
     LastInstructionExecuted,
+
+    /// A `hlt` instruction was executed
+    Halted,
+
+    /// The program did something invalid, and can't continue
+    Fault(Fault),
 }
 
+/// How a run of the program ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Reached the end of the program
+    Terminated,
+
+    /// Executed `hlt`
+    Halted,
+
+    /// Would go round `Cycle` forever
+    Looped(Cycle),
+
+    Faulted(Fault),
+
+    /// Executed the most instructions it was allowed to without stopping
+    BudgetExhausted,
+
+    /// Ran out of time without stopping
+    TimedOut,
+}
+
+impl Outcome {
+    /// Whether the program stopped normally
+    pub fn terminated(&self) -> bool {
+        matches!(self, Outcome::Terminated | Outcome::Halted)
+    }
+}
+
+/// An infinite loop found by `Vm::run`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cycle {
+    /// The address the loop starts (and would start again) at
+    pub entry: usize,
+
+    /// Every address executed once round the loop, in order, starting with `entry`.
+    ///
+    /// If `LoopDetection::Address` is forced on a program with conditional
+    /// jumps and the loop was entered before `run`, the path from `entry` may
+    /// never come back to it. Then this is just its first `len` addresses.
+    pub body: Vec<usize>,
+
+    /// How much the accumulator goes up each time round
+    pub acc_delta: i64,
+
+    /// The addresses executed before first reaching `entry`
+    pub prefix: Vec<usize>,
+}
+
+/// Something a program did that stops it without terminating normally.
+/// Each fault records the address of the instruction that caused it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// A jump to before the first instruction
+    NegativeJump { address: usize, offset: isize },
+
+    /// A jump past the end of the program which the termination policy
+    /// doesn't allow
+    JumpOutOfRange { address: usize, offset: isize },
+
+    /// An arithmetic result that doesn't fit in an `i32`
+    Overflow { address: usize, reg: Reg },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Fault::NegativeJump { address, offset } => {
+                write!(
+                    f,
+                    "jump by {:+} at {:04} is before the start",
+                    offset, address
+                )
+            }
+            Fault::JumpOutOfRange { address, offset } => {
+                write!(f, "jump by {:+} at {:04} is past the end", offset, address)
+            }
+            Fault::Overflow { address, reg } => {
+                write!(f, "register {} overflowed at {:04}", reg.name(), address)
+            }
+        }
+    }
+}
+
+impl Error for Fault {}
+
+/// How the VM decides a program is stuck in a loop.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoopDetection {
+    /// Executing any address twice is a loop. This is exact for programs
+    /// without conditional jumps, which always go the same way.
+    Address,
+
+    /// Executing an address twice with the same registers is a loop. This is
+    /// exact for any program, but remembers every state it passes through.
+    State,
+
+    /// Never detect loops, and don't remember what's been executed. Programs
+    /// that loop will then only stop with a step budget or timeout.
+    Off,
+}
+
+/// Which ways of leaving the program count as terminating normally, besides
+/// `hlt` and reaching the address just past the last instruction.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TerminationPolicy {
+    /// Jumping any further past the end is a `Fault::JumpOutOfRange`
+    #[default]
+    Exact,
+
+    /// Jumping anywhere past the end terminates
+    PastEnd,
+}
+
+/// A virtual machine running one program.
+///
+/// The program is either owned (`Vm::new`) or borrowed (`Vm::borrowed`), so
+/// many runs can share it. Patches are kept separately and never touch the
+/// underlying program.
 #[derive(Clone, Debug)]
-pub struct Vm {
-    instructions: Vec<Instruction>,
+pub struct Vm<'a> {
+    program: Cow<'a, [Instruction]>,
+
+    /// Instructions replaced with `patch`, at most one per address
+    patches: Vec<(usize, Instruction)>,
     current_instruction: usize,
-    accumulator: i32,
-    instructions_executed: HashSet<usize>,
+    registers: [i32; NUM_REGISTERS],
+    halted: bool,
+    fault: Option<Fault>,
+    policy: TerminationPolicy,
+    output: Vec<i32>,
+    instructions_executed: BitSet,
+
+    /// Every `(address, registers)` state executed so far, when detecting
+    /// loops by `LoopDetection::State`
+    states_seen: HashSet<(usize, [i32; NUM_REGISTERS])>,
+
+    /// Whether the program (with patches) has a `jz` or `jnz`
+    has_conditional: bool,
+
+    /// Set by `set_loop_detection`; otherwise it depends on `has_conditional`
+    loop_detection: Option<LoopDetection>,
+    trace: Option<Vec<TraceEntry>>,
+
+    /// The unpatched program in the form `run` uses, compiled the first time
+    /// it's needed, or `None` if it can't be
+    compiled: OnceCell<Option<Compiled>>,
+
+    /// The path taken by the last compiled run, kept so that running again
+    /// doesn't allocate it again
+    compiled_path: Vec<Stretch>,
 }
 
-impl Vm {
+/// The state of a `Vm` at one point in a run, to go back to with `restore`.
+///
+/// This is everything but the program, its patches, and the trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    current_instruction: usize,
+    registers: [i32; NUM_REGISTERS],
+    halted: bool,
+    fault: Option<Fault>,
+    output: Vec<i32>,
+    instructions_executed: BitSet,
+    states_seen: HashSet<(usize, [i32; NUM_REGISTERS])>,
+}
+
+impl<'a> Vm<'a> {
     pub fn new<I: Into<Vec<Instruction>>>(instructions: I) -> Self {
-        let instructions = instructions.into();
+        Self::with_program(Cow::Owned(instructions.into()))
+    }
+
+    /// A VM running a program it doesn't own
+    pub fn borrowed(program: &'a [Instruction]) -> Self {
+        Self::with_program(Cow::Borrowed(program))
+    }
+
+    fn with_program(program: Cow<'a, [Instruction]>) -> Self {
+        let has_conditional = program.iter().any(|ins| ins.is_conditional());
 
         Self {
-            instructions,
+            instructions_executed: BitSet::new(program.len()),
+            program,
+            patches: vec![],
             current_instruction: 0,
-            accumulator: 0,
-            instructions_executed: HashSet::new(),
+            registers: [0; NUM_REGISTERS],
+            halted: false,
+            fault: None,
+            policy: TerminationPolicy::default(),
+            output: vec![],
+            states_seen: HashSet::new(),
+            has_conditional,
+            loop_detection: None,
+            trace: None,
+            compiled: OnceCell::new(),
+            compiled_path: vec![],
         }
     }
 
     fn fetch(&mut self) -> Result<Instruction, StepError> {
         let ins_num = self.current_instruction;
 
-        // The number immediately following the last instruction is `self.instructions.len()`
-        if ins_num == self.instructions.len() {
+        if let Some(fault) = self.fault {
+            return Err(StepError::Fault(fault));
+        }
+
+        if self.halted {
+            return Err(StepError::Halted);
+        }
+
+        // The number immediately following the last instruction is `self.len()`.
+        // Only `TerminationPolicy::PastEnd` can leave us further along than that.
+        if ins_num >= self.len() {
             return Err(StepError::LastInstructionExecuted);
         }
 
-        // Try inserting instruction number
-        if !self.instructions_executed.insert(ins_num) {
-            // Instruction number was already present
+        let repeated = match self.loop_detection() {
+            LoopDetection::Address => !self.instructions_executed.insert(ins_num),
+            LoopDetection::State => {
+                self.instructions_executed.insert(ins_num);
+                !self.states_seen.insert((ins_num, self.registers))
+            }
+            LoopDetection::Off => false,
+        };
+
+        if repeated {
             return Err(StepError::InstructionAlreadyExecuted(ins_num));
         }
 
-        Ok(self.instructions[self.current_instruction])
+        Ok(self.instruction(ins_num).unwrap())
     }
 
-    fn execute(&mut self, instruction: Instruction) {
-        self.instructions_executed.insert(self.current_instruction);
-
+    /// Execute `instruction`, leaving the VM untouched if it faults
+    fn execute(&mut self, instruction: Instruction) -> Result<(), Fault> {
         match instruction {
-            Instruction::Acc(x) => {
-                self.accumulator += x;
-                self.current_instruction += 1;
-            }
+            Instruction::Acc(x) => self.arithmetic(Reg::ACC, |v| v.checked_add(x))?,
+            Instruction::Jmp(x) => return self.jump(x),
+            Instruction::Nop(_) => { /* do nothing */ }
+            Instruction::Add(r, x) => self.arithmetic(r, |v| v.checked_add(x))?,
+            Instruction::Mul(r, x) => self.arithmetic(r, |v| v.checked_mul(x))?,
+            Instruction::Set(r, x) => self.registers[r.index()] = x,
 
-            Instruction::Jmp(x) => {
-                let new_instruction = (self.current_instruction as isize) + x;
-
-                if new_instruction.is_negative() {
-                    panic!("attempt to jump to negative instruction");
+            Instruction::Jz(r, x) => {
+                if self.registers[r.index()] == 0 {
+                    return self.jump(x);
                 }
+            }
 
-                self.current_instruction = new_instruction as usize;
+            Instruction::Jnz(r, x) => {
+                if self.registers[r.index()] != 0 {
+                    return self.jump(x);
+                }
             }
 
-            Instruction::Nop(_) => {
-                /* do nothing */
-                self.current_instruction += 1;
+            Instruction::Out(r) => self.output.push(self.registers[r.index()]),
+
+            Instruction::Hlt => {
+                self.halted = true;
+                return Ok(());
             }
         }
+
+        self.current_instruction += 1;
+        Ok(())
+    }
+
+    fn arithmetic<F>(&mut self, reg: Reg, op: F) -> Result<(), Fault>
+    where
+        F: FnOnce(i32) -> Option<i32>,
+    {
+        let address = self.current_instruction;
+        let value = op(self.registers[reg.index()]).ok_or(Fault::Overflow { address, reg })?;
+
+        self.registers[reg.index()] = value;
+        Ok(())
+    }
+
+    fn jump(&mut self, offset: isize) -> Result<(), Fault> {
+        let address = self.current_instruction;
+
+        // A target too far away to work out is out of range either way
+        let target = match (address as isize).checked_add(offset) {
+            Some(target) if !target.is_negative() => target,
+            _ if offset < 0 => return Err(Fault::NegativeJump { address, offset }),
+            _ => return Err(Fault::JumpOutOfRange { address, offset }),
+        };
+
+        let target = target as usize;
+
+        if target > self.len() && self.policy == TerminationPolicy::Exact {
+            return Err(Fault::JumpOutOfRange { address, offset });
+        }
+
+        self.current_instruction = target;
+        Ok(())
     }
 
     pub fn step(&mut self) -> Result<(), StepError> {
         let next_instruction = self.fetch()?;
-        self.execute(next_instruction);
+        let address = self.current_instruction;
+        let acc_before = self.acc();
+
+        if let Err(fault) = self.execute(next_instruction) {
+            self.fault = Some(fault);
+            return Err(StepError::Fault(fault));
+        }
+
+        let acc_after = self.acc();
+
+        if let Some(trace) = &mut self.trace {
+            trace.push(TraceEntry {
+                address,
+                instruction: next_instruction,
+                acc_before,
+                acc_after,
+            });
+        }
+
         Ok(())
     }
 
+    /// Start recording every instruction executed from now on.
+    pub fn enable_trace(&mut self) {
+        if self.trace.is_none() {
+            self.trace = Some(vec![]);
+        }
+    }
+
+    /// The instructions executed since `enable_trace`, in order
+    pub fn trace(&self) -> Option<&[TraceEntry]> {
+        self.trace.as_deref()
+    }
+
+    /// Stop tracing, returning everything recorded so far
+    pub fn take_trace(&mut self) -> Option<Vec<TraceEntry>> {
+        self.trace.take()
+    }
+
+    /// Run until the program terminates (true) or loops or faults (false).
     pub fn terminates(&mut self) -> bool {
-        use StepError::*;
+        self.terminates_within(None)
+    }
+
+    /// Like `terminates`, but false if it takes more than `max_steps`
+    /// steps. A loop found by a compiled run isn't worked out, since
+    /// nothing needs it.
+    pub(crate) fn terminates_within(&mut self, max_steps: Option<u64>) -> bool {
+        match self.run_compiled(max_steps, None) {
+            Some(exit) => matches!(exit, Exit::Terminated(_)),
+            None => self.run_stepped(max_steps, None).terminated(),
+        }
+    }
+
+    /// Run until the program stops, and report how.
+    ///
+    /// A loop's prefix only covers instructions executed by this call, so
+    /// run from a fresh (or `reset`) VM to see the whole path into it.
+    ///
+    /// From a fresh VM, programs using only `acc`, `jmp` and `nop` are run
+    /// a basic block at a time, which is much faster on long programs.
+    pub fn run(&mut self) -> Outcome {
+        self.run_limited(None, None)
+    }
+
+    /// Like `run`, but give up after executing `max_steps` instructions
+    pub fn run_with_budget(&mut self, max_steps: u64) -> Outcome {
+        self.run_limited(Some(max_steps), None)
+    }
+
+    /// Like `run`, but give up once `timeout` has passed
+    pub fn run_with_timeout(&mut self, timeout: Duration) -> Outcome {
+        self.run_limited(None, Some(Instant::now() + timeout))
+    }
+
+    fn run_limited(&mut self, max_steps: Option<u64>, deadline: Option<Instant>) -> Outcome {
+        match self.run_compiled(max_steps, deadline) {
+            Some(Exit::Terminated(_)) => Outcome::Terminated,
+            Some(Exit::Looped(entry)) => {
+                let code = self.compiled.get().and_then(Option::as_ref).unwrap();
+                Outcome::Looped(code.cycle(&self.compiled_path, entry, self.acc()))
+            }
+            Some(_) => Outcome::Faulted(self.fault.unwrap()),
+            None => self.run_stepped(max_steps, deadline),
+        }
+    }
+
+    /// `run_limited` a step at a time
+    fn run_stepped(&mut self, max_steps: Option<u64>, deadline: Option<Instant>) -> Outcome {
+        // Checking the clock every step would slow short steps down a lot
+        const CLOCK_INTERVAL: u64 = 1024;
+
+        // Every state executed by this call, to find where a loop began
+        let record_path = self.loop_detection() != LoopDetection::Off;
+        let mut path = vec![];
+        let mut steps = 0;
 
         loop {
-            if let Err(e) = self.step() {
-                match e {
-                    InstructionAlreadyExecuted(_) => {
-                        return false;
-                    }
+            // Only give up if there's something left to run
+            let running = self.fault.is_none() && !self.halted && self.pc() < self.len();
+
+            if running && max_steps.is_some_and(|max| steps >= max) {
+                return Outcome::BudgetExhausted;
+            }
+
+            if running
+                && steps % CLOCK_INTERVAL == 0
+                && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return Outcome::TimedOut;
+            }
 
-                    LastInstructionExecuted => {
-                        return true;
+            let state = (self.current_instruction, self.registers);
+
+            match self.step() {
+                Ok(()) => {
+                    steps += 1;
+                    if record_path {
+                        path.push(state);
                     }
                 }
+                Err(StepError::LastInstructionExecuted) => return Outcome::Terminated,
+                Err(StepError::Halted) => return Outcome::Halted,
+                Err(StepError::Fault(fault)) => return Outcome::Faulted(fault),
+                Err(StepError::InstructionAlreadyExecuted(entry)) => {
+                    return Outcome::Looped(self.cycle(entry, &path));
+                }
             }
         }
     }
 
-    pub fn step_until_loop(&mut self) {
-        use StepError::InstructionAlreadyExecuted as AlreadyExec;
+    /// `run_limited` a basic block at a time, for a fresh VM running the
+    /// puzzle's instructions with nothing else switched on. Ends in exactly
+    /// the same state as running it a step at a time would, and returns how
+    /// (which is never `MightOverflow`). A loop's path is left in
+    /// `compiled_path`.
+    ///
+    /// Returns `None`, leaving the VM as it was, if it can't be used.
+    fn run_compiled(&mut self, max_steps: Option<u64>, deadline: Option<Instant>) -> Option<Exit> {
+        // A compiled run executes each address at most once, so it can't
+        // use up a budget as big as the program
+        let len = self.len() as u64;
+        if deadline.is_some() || max_steps.is_some_and(|max| max < len) {
+            return None;
+        }
 
-        loop {
-            if let Err(AlreadyExec(_)) = self.step() {
-                return;
+        let fresh = self.current_instruction == 0
+            && self.registers == [0; NUM_REGISTERS]
+            && !self.halted
+            && self.fault.is_none()
+            && self.instructions_executed.is_empty();
+
+        let puzzle_patches = self
+            .patches
+            .iter()
+            .all(|&(_, ins)| ins.instruction_set() == InstructionSet::V1);
+
+        if !fresh
+            || !puzzle_patches
+            || self.has_conditional
+            || self.trace.is_some()
+            || self.loop_detection() != LoopDetection::Address
+        {
+            return None;
+        }
+
+        let program = &self.program;
+        let code = self.compiled.get_or_init(|| Compiled::new(program));
+        let code = code.as_ref()?;
+
+        let past_end = self.policy == TerminationPolicy::PastEnd;
+        let mut acc = 0;
+        let path = &mut self.compiled_path;
+        let visited = &mut self.instructions_executed;
+
+        path.clear();
+
+        let exit = code.run(&self.patches, &mut acc, visited, path, past_end);
+
+        match exit {
+            Exit::Terminated(address) | Exit::Looped(address) => {
+                self.current_instruction = address;
+            }
+
+            Exit::BadJump(address) => {
+                let offset = match self.instruction(address) {
+                    Some(Instruction::Jmp(x)) => x,
+                    ins => unreachable!("`{:?}` can't jump out of range", ins),
+                };
+
+                let fault = if offset < 0 {
+                    Fault::NegativeJump { address, offset }
+                } else {
+                    Fault::JumpOutOfRange { address, offset }
+                };
+
+                self.current_instruction = address;
+                self.fault = Some(fault);
+            }
+
+            // Only a step at a time can find exactly where it overflows
+            Exit::MightOverflow(_) => {
+                self.instructions_executed.clear();
+                return None;
+            }
+        }
+
+        self.registers[Reg::ACC.index()] = acc;
+        Some(exit)
+    }
+
+    /// The cycle starting at `entry`, where the VM is now about to repeat
+    /// itself, given the states executed by `run` so far
+    fn cycle(&self, entry: usize, path: &[(usize, [i32; NUM_REGISTERS])]) -> Cycle {
+        let by_state = self.loop_detection() == LoopDetection::State;
+        let is_entry = |&(address, registers): &(usize, [i32; NUM_REGISTERS])| {
+            address == entry && (!by_state || registers == self.registers)
+        };
+
+        if let Some(start) = path.iter().position(is_entry) {
+            return Cycle {
+                entry,
+                body: path[start..].iter().map(|&(a, _)| a).collect(),
+                acc_delta: self.acc() as i64 - path[start].1[Reg::ACC.index()] as i64,
+                prefix: path[..start].iter().map(|&(a, _)| a).collect(),
+            };
+        }
+
+        // We entered the loop before `run` was called, so go round it again
+        let mut vm = self.clone();
+        vm.trace = None;
+        vm.loop_detection = Some(LoopDetection::Off);
+
+        // Going by addresses alone, the path comes back within one pass over
+        // the program if at all. Going by state, every state on the loop has
+        // been recorded. Either way, patches made since may stop it coming
+        // back, so report only so much of the path.
+        let limit = if by_state && self.has_conditional {
+            self.states_seen.len()
+        } else {
+            self.len()
+        };
+
+        let mut body = vec![];
+        while vm.current_instruction != entry || body.is_empty() {
+            if body.len() >= limit {
+                break;
             }
+
+            body.push(vm.current_instruction);
+
+            if self.has_conditional {
+                // Only a repeated state gets here, and that went round
+                // without faulting last time
+                if vm.step().is_err() {
+                    break;
+                }
+            } else {
+                // The accumulator may differ from last time round, so avoid
+                // overflowing by only following addresses
+                let ins = vm.instruction(vm.current_instruction).unwrap();
+                match cfg::successor(ins, vm.current_instruction, vm.len()) {
+                    Some(next) => vm.current_instruction = next,
+                    // A patch made since leads out of the loop
+                    None => break,
+                }
+            }
+        }
+
+        let acc_delta = if self.has_conditional {
+            vm.acc() as i64 - self.acc() as i64
+        } else {
+            body.iter()
+                .map(|&a| match self.instruction(a).unwrap() {
+                    Instruction::Acc(x) => x as i64,
+                    _ => 0,
+                })
+                .sum()
+        };
+
+        Cycle {
+            entry,
+            body,
+            acc_delta,
+            prefix: vec![],
         }
     }
 
+    /// How loops are detected. Unless changed with `set_loop_detection`, this
+    /// is `State` for programs with conditional jumps and `Address` otherwise.
+    pub fn loop_detection(&self) -> LoopDetection {
+        match self.loop_detection {
+            Some(mode) => mode,
+            None if self.has_conditional => LoopDetection::State,
+            None => LoopDetection::Address,
+        }
+    }
+
+    /// Change how loops are detected from the next step on. Turning detection
+    /// off also stops `visited` being kept up to date.
+    pub fn set_loop_detection(&mut self, mode: LoopDetection) {
+        self.loop_detection = Some(mode);
+    }
+
+    /// Run until just before an instruction would execute a second time,
+    /// or until the program stops some other way.
+    pub fn step_until_loop(&mut self) {
+        while self.step().is_ok() {}
+    }
+
     pub fn acc(&self) -> i32 {
-        self.accumulator
+        self.register(Reg::ACC)
+    }
+
+    pub fn register(&self, reg: Reg) -> i32 {
+        self.registers[reg.index()]
+    }
+
+    /// Everything written by `out` instructions, in order
+    pub fn output(&self) -> &[i32] {
+        &self.output
+    }
+
+    /// Whether a `hlt` instruction has been executed
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// The fault that stopped the program, if any
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+
+    pub fn termination_policy(&self) -> TerminationPolicy {
+        self.policy
+    }
+
+    pub fn set_termination_policy(&mut self, policy: TerminationPolicy) {
+        self.policy = policy;
+    }
+
+    /// Address of the next instruction to execute
+    pub fn pc(&self) -> usize {
+        self.current_instruction
+    }
+
+    /// Number of instructions in the program
+    pub fn len(&self) -> usize {
+        self.program.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.program.is_empty()
+    }
+
+    /// The instruction at `address`, with patches applied
+    pub fn instruction(&self, address: usize) -> Option<Instruction> {
+        match self.patches.iter().find(|&&(a, _)| a == address) {
+            Some(&(_, ins)) => Some(ins),
+            None => self.program.get(address).copied(),
+        }
+    }
+
+    /// The whole program with patches applied. This only copies the program
+    /// if it has been patched.
+    pub fn instructions(&self) -> Cow<'_, [Instruction]> {
+        if self.patches.is_empty() {
+            return Cow::Borrowed(&self.program);
+        }
+
+        let mut patched = self.program.to_vec();
+        for &(address, ins) in self.patches.iter() {
+            patched[address] = ins;
+        }
+
+        Cow::Owned(patched)
+    }
+
+    /// Addresses that have been executed so far, in address order.
+    /// Nothing is recorded while loop detection is off.
+    pub fn visited(&self) -> Vec<usize> {
+        self.instructions_executed.iter().collect()
+    }
+
+    pub fn has_visited(&self, address: usize) -> bool {
+        self.instructions_executed.contains(address)
+    }
+
+    /// Replace the instruction at `address`, returning the old one,
+    /// or `None` if there's no instruction there.
+    ///
+    /// Patches last until `clear_patches`, through any number of `reset`s.
+    pub fn patch(&mut self, address: usize, instruction: Instruction) -> Option<Instruction> {
+        let old = self.instruction(address)?;
+        self.has_conditional |= instruction.is_conditional();
+
+        match self.patches.iter_mut().find(|(a, _)| *a == address) {
+            Some(patch) => patch.1 = instruction,
+            None => self.patches.push((address, instruction)),
+        }
+
+        Some(old)
+    }
+
+    /// Every patched address and the instruction now there, in patch order
+    pub fn patches(&self) -> &[(usize, Instruction)] {
+        &self.patches
+    }
+
+    /// Undo every patch, going back to the original program
+    pub fn clear_patches(&mut self) {
+        self.patches.clear();
+
+        // Clearing patches can't add a conditional jump
+        if self.has_conditional {
+            self.has_conditional = self.program.iter().any(|ins| ins.is_conditional());
+        }
+    }
+
+    /// Restart the program from the beginning, keeping the program, patches,
+    /// termination policy and whether tracing is on. Nothing is reallocated.
+    pub fn reset(&mut self) {
+        self.current_instruction = 0;
+        self.registers = [0; NUM_REGISTERS];
+        self.halted = false;
+        self.fault = None;
+        self.output.clear();
+        self.instructions_executed.clear();
+        self.states_seen.clear();
+
+        if let Some(trace) = &mut self.trace {
+            trace.clear();
+        }
+    }
+
+    /// Save the current state, so the run can be rewound to here with `restore`
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            current_instruction: self.current_instruction,
+            registers: self.registers,
+            halted: self.halted,
+            fault: self.fault,
+            output: self.output.clone(),
+            instructions_executed: self.instructions_executed.clone(),
+            states_seen: self.states_seen.clone(),
+        }
+    }
+
+    /// Go back to the state saved in `snapshot`. The program and patches are
+    /// left as they are, and traced instructions aren't forgotten.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.current_instruction = snapshot.current_instruction;
+        self.registers = snapshot.registers;
+        self.halted = snapshot.halted;
+        self.fault = snapshot.fault;
+        self.output.clone_from(&snapshot.output);
+        self.instructions_executed
+            .clone_from(&snapshot.instructions_executed);
+        self.states_seen.clone_from(&snapshot.states_seen);
     }
 }
 
+/// Parse the puzzle's `op ±n` lines, panicking on invalid input.
+/// See `asm::assemble` for labels, comments and error reporting.
 pub fn parse_instructions(instructions: &str) -> Vec<Instruction> {
-    instructions
-        .lines()
-        .map(|inst| {
-            let mut parts = inst.split_whitespace();
-            let op = parts.next().unwrap();
-            let arg = parts.next().unwrap();
-
-            match op {
-                "acc" => {
-                    let inc = arg.parse().unwrap();
-                    Instruction::Acc(inc)
-                }
-
-                "jmp" => {
-                    let amt = arg.parse().unwrap();
-                    Instruction::Jmp(amt)
-                }
+    match asm::assemble(instructions) {
+        Ok(instructions) => instructions,
+        Err(e) => panic!("{}", e),
+    }
+}
 
-                "nop" => {
-                    let arg = arg.parse().unwrap();
-                    Instruction::Nop(arg)
-                }
+/// The example program from the puzzle, for tests throughout the crate
+#[cfg(test)]
+pub(crate) fn example() -> Vec<Instruction> {
+    use Instruction::*;
 
-                x => panic!("invalid opcode {}", x),
-            }
-        })
-        .collect()
+    vec![
+        Nop(0),
+        Acc(1),
+        Jmp(4),
+        Acc(3),
+        Jmp(-3),
+        Acc(-99),
+        Acc(1),
+        Jmp(-4),
+        Acc(6),
+    ]
 }
 
 #[cfg(test)]
@@ -246,4 +1088,537 @@ mod tests {
         let mut vm = Vm::new(instructions);
         assert!(vm.terminates())
     }
+
+    #[test]
+    fn test_registers_and_output() {
+        use Instruction::*;
+
+        let (a, b) = (Reg::A, Reg::B);
+
+        // a = 2 * 3, counting b down from 3
+        let mut vm = Vm::new([
+            Set(b, 3),
+            Add(a, 2),
+            Add(b, -1),
+            Jnz(b, -2),
+            Out(a),
+            Mul(a, -1),
+            Out(a),
+            Hlt,
+            Acc(100),
+        ]);
+
+        assert!(vm.terminates());
+        assert!(vm.is_halted());
+        assert_eq!(vm.acc(), -6);
+        assert_eq!(vm.register(b), 0);
+        assert_eq!(vm.output(), &[6, -6]);
+        assert_eq!(vm.pc(), 7);
+        assert_eq!(vm.step(), Err(StepError::Halted));
+    }
+
+    #[test]
+    fn test_conditional_loop_detection() {
+        use Instruction::*;
+
+        // Revisiting address 1 isn't a loop while `b` changes, but once `b`
+        // is zero, `jnz` and `jz` bounce between each other forever
+        let b = Reg::B;
+        let mut vm = Vm::new([Set(b, 2), Add(b, -1), Jnz(b, -1), Jz(b, -1)]);
+
+        assert!(!vm.terminates());
+        assert_eq!(vm.register(b), 0);
+        assert_eq!(vm.pc(), 2);
+    }
+
+    #[test]
+    fn test_instruction_set() {
+        use Instruction::*;
+
+        assert_eq!(InstructionSet::required_by(&[]), InstructionSet::V1);
+        assert_eq!(
+            InstructionSet::required_by(&[Acc(1), Jmp(-1)]),
+            InstructionSet::V1
+        );
+        assert_eq!(
+            InstructionSet::required_by(&[Acc(1), Hlt]),
+            InstructionSet::V2
+        );
+        assert!(!InstructionSet::V1.supports(Out(Reg::A)));
+        assert_eq!(InstructionSet::from_version(2), Some(InstructionSet::V2));
+        assert_eq!(InstructionSet::from_version(3), None);
+        assert_eq!(Reg::from_name("c"), Some(Reg::C));
+        assert_eq!(Reg::from_name("e"), None);
+    }
+
+    #[test]
+    fn test_negative_jump_faults() {
+        use Instruction::*;
+
+        let mut vm = Vm::new([Acc(1), Jmp(-2)]);
+        let fault = Fault::NegativeJump {
+            address: 1,
+            offset: -2,
+        };
+
+        assert!(!vm.terminates());
+        assert_eq!(vm.fault(), Some(fault));
+        assert_eq!(vm.pc(), 1);
+        assert_eq!(vm.step(), Err(StepError::Fault(fault)));
+    }
+
+    #[test]
+    fn test_jump_past_end() {
+        use Instruction::*;
+
+        let program = [Acc(1), Jmp(5), Acc(1)];
+
+        let mut vm = Vm::new(program);
+        assert!(!vm.terminates());
+        assert_eq!(
+            vm.fault(),
+            Some(Fault::JumpOutOfRange {
+                address: 1,
+                offset: 5
+            })
+        );
+
+        let mut vm = Vm::new(program);
+        vm.set_termination_policy(TerminationPolicy::PastEnd);
+        assert!(vm.terminates());
+        assert_eq!(vm.fault(), None);
+        assert_eq!(vm.pc(), 6);
+        assert_eq!(vm.step(), Err(StepError::LastInstructionExecuted));
+    }
+
+    #[test]
+    fn test_overflowing_jump_faults() {
+        use Instruction::*;
+
+        for &policy in [TerminationPolicy::Exact, TerminationPolicy::PastEnd].iter() {
+            let mut vm = Vm::new([Nop(0), Jmp(isize::MAX)]);
+            vm.set_termination_policy(policy);
+            vm.step().unwrap();
+
+            let fault = Fault::JumpOutOfRange {
+                address: 1,
+                offset: isize::MAX,
+            };
+            assert_eq!(vm.step(), Err(StepError::Fault(fault)));
+        }
+
+        let mut vm = Vm::new([Nop(0), Jmp(isize::MIN)]);
+        vm.step().unwrap();
+
+        let fault = Fault::NegativeJump {
+            address: 1,
+            offset: isize::MIN,
+        };
+        assert_eq!(vm.step(), Err(StepError::Fault(fault)));
+    }
+
+    #[test]
+    fn test_overflow_faults() {
+        use Instruction::*;
+
+        let mut vm = Vm::new([Acc(i32::MAX), Acc(1)]);
+        assert!(!vm.terminates());
+        assert_eq!(
+            vm.fault(),
+            Some(Fault::Overflow {
+                address: 1,
+                reg: Reg::A
+            })
+        );
+        assert_eq!(vm.acc(), i32::MAX);
+
+        let mut vm = Vm::new([Set(Reg::C, 1 << 20), Mul(Reg::C, 1 << 12)]);
+        assert!(!vm.terminates());
+        assert_eq!(
+            vm.fault().unwrap().to_string(),
+            "register c overflowed at 0001"
+        );
+    }
+
+    #[test]
+    fn test_step_until_loop_stops_at_end() {
+        use Instruction::*;
+
+        let mut vm = Vm::new([Acc(2), Jmp(-3)]);
+        vm.step_until_loop();
+        assert_eq!(vm.acc(), 2);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut vm = Vm::new(example());
+        vm.enable_trace();
+        vm.step_until_loop();
+        assert_eq!(vm.acc(), 5);
+
+        vm.reset();
+        assert_eq!(vm.pc(), 0);
+        assert_eq!(vm.acc(), 0);
+        assert!(vm.visited().is_empty());
+        assert_eq!(vm.trace(), Some(&[][..]));
+
+        vm.step_until_loop();
+        assert_eq!(vm.acc(), 5);
+    }
+
+    #[test]
+    fn test_borrowed_with_patches() {
+        use Instruction::*;
+
+        let program = example();
+        let mut vm = Vm::borrowed(&program);
+
+        assert_eq!(vm.patch(7, Nop(-4)), Some(Jmp(-4)));
+        assert_eq!(vm.patch(7, Nop(-5)), Some(Nop(-4)));
+        assert_eq!(vm.patch(9, Nop(0)), None);
+        assert_eq!(vm.patches(), &[(7, Nop(-5))]);
+        assert_eq!(vm.instruction(7), Some(Nop(-5)));
+        assert_eq!(vm.instructions()[7], Nop(-5));
+        assert!(vm.terminates());
+        assert_eq!(vm.acc(), 8);
+
+        // The borrowed program is untouched
+        assert_eq!(program[7], Jmp(-4));
+
+        vm.clear_patches();
+        vm.reset();
+        assert!(!vm.terminates());
+        assert_eq!(vm.acc(), 5);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        use Instruction::*;
+
+        let program = [
+            Set(Reg::B, 2),
+            Out(Reg::B),
+            Add(Reg::B, -1),
+            Jnz(Reg::B, -2),
+        ];
+        let mut vm = Vm::borrowed(&program);
+
+        vm.step().unwrap();
+        let snapshot = vm.snapshot();
+
+        assert!(vm.terminates());
+        assert_eq!(vm.output(), &[2, 1]);
+
+        vm.restore(&snapshot);
+        assert_eq!(vm.pc(), 1);
+        assert_eq!(vm.register(Reg::B), 2);
+        assert!(vm.output().is_empty());
+        assert_eq!(vm.visited(), vec![0]);
+
+        // Rewinding and taking another path
+        vm.patch(2, Add(Reg::B, -2));
+        assert!(vm.terminates());
+        assert_eq!(vm.output(), &[2]);
+    }
+
+    #[test]
+    fn test_run_reports_cycle() {
+        let mut vm = Vm::new(example());
+
+        assert_eq!(
+            vm.run(),
+            Outcome::Looped(Cycle {
+                entry: 1,
+                body: vec![1, 2, 6, 7, 3, 4],
+                acc_delta: 5,
+                prefix: vec![0],
+            })
+        );
+        assert_eq!(vm.pc(), 1);
+        assert_eq!(vm.acc(), 5);
+    }
+
+    #[test]
+    fn test_run_outcomes() {
+        use Instruction::*;
+
+        assert_eq!(Vm::new([Acc(1)]).run(), Outcome::Terminated);
+        assert_eq!(Vm::new([Hlt, Jmp(0)]).run(), Outcome::Halted);
+        assert_eq!(
+            Vm::new([Jmp(-1)]).run(),
+            Outcome::Faulted(Fault::NegativeJump {
+                address: 0,
+                offset: -1
+            })
+        );
+    }
+
+    #[test]
+    fn test_cycle_entered_before_run() {
+        let mut vm = Vm::new(example());
+
+        // Already inside the loop at address 6
+        for _ in 0..4 {
+            vm.step().unwrap();
+        }
+
+        assert_eq!(
+            vm.run(),
+            Outcome::Looped(Cycle {
+                entry: 1,
+                body: vec![1, 2, 6, 7, 3, 4],
+                acc_delta: 5,
+                prefix: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn test_address_cycle_that_never_returns() {
+        use Instruction::*;
+
+        // The second time round, `jnz` goes on to `jmp +0` instead
+        let program = [Jnz(Reg::B, 3), Add(Reg::B, 1), Jmp(-2), Jmp(0)];
+        let mut vm = Vm::borrowed(&program);
+        vm.set_loop_detection(LoopDetection::Address);
+
+        for _ in 0..3 {
+            vm.step().unwrap();
+        }
+
+        match vm.run() {
+            Outcome::Looped(cycle) => {
+                assert_eq!(cycle.entry, 0);
+                assert_eq!(cycle.body, vec![0, 3, 3, 3]);
+            }
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cycle_patched_to_jump_away() {
+        use Instruction::*;
+
+        let mut vm = Vm::new([Nop(0), Acc(1), Jmp(-1), Acc(6)]);
+        for _ in 0..3 {
+            vm.step().unwrap();
+        }
+
+        // The loop that was entered now jumps before the start
+        vm.patch(1, Jmp(-5));
+        assert_eq!(
+            vm.run(),
+            Outcome::Looped(Cycle {
+                entry: 1,
+                body: vec![1],
+                acc_delta: 0,
+                prefix: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn test_state_cycle_patched_to_never_return() {
+        use Instruction::*;
+
+        let mut vm = Vm::new([Nop(0), Nop(0), Jmp(-1), Jmp(0)]);
+        vm.set_loop_detection(LoopDetection::State);
+        for _ in 0..3 {
+            vm.step().unwrap();
+        }
+
+        // Address 1 now leads to `jmp +0`, which never gets back to it
+        vm.patch(1, Jmp(2));
+        match vm.run() {
+            Outcome::Looped(cycle) => assert_eq!(cycle.body, vec![1, 3, 3, 3]),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_conditional_cycle() {
+        use Instruction::*;
+
+        let b = Reg::B;
+        let mut vm = Vm::new([Set(b, 2), Acc(3), Add(b, -1), Jnz(b, -2), Jz(b, -1)]);
+
+        // b counts down to zero, then jnz and jz bounce between each other
+        assert_eq!(
+            vm.run(),
+            Outcome::Looped(Cycle {
+                entry: 3,
+                body: vec![3, 4],
+                acc_delta: 0,
+                prefix: vec![0, 1, 2, 3, 1, 2],
+            })
+        );
+    }
+
+    #[test]
+    fn test_run_with_budget() {
+        use Instruction::*;
+
+        // Counts b up until it overflows, billions of steps away
+        let program = [Add(Reg::B, 1), Jnz(Reg::B, -1), Acc(1)];
+
+        let mut vm = Vm::borrowed(&program);
+        assert_eq!(vm.run_with_budget(1000), Outcome::BudgetExhausted);
+        assert_eq!(vm.register(Reg::B), 500);
+
+        // A budget that's just enough doesn't count as exhausted
+        let mut vm = Vm::new([Acc(1), Acc(2)]);
+        assert_eq!(vm.run_with_budget(2), Outcome::Terminated);
+
+        let mut vm = Vm::new(example());
+        assert!(matches!(vm.run_with_budget(100), Outcome::Looped(_)));
+    }
+
+    #[test]
+    fn test_run_with_timeout() {
+        use Instruction::*;
+
+        let mut vm = Vm::new([Jmp(0)]);
+        vm.set_loop_detection(LoopDetection::Off);
+
+        assert_eq!(
+            vm.run_with_timeout(Duration::from_millis(10)),
+            Outcome::TimedOut
+        );
+        assert!(vm.visited().is_empty());
+    }
+
+    #[test]
+    fn test_loop_detection_modes() {
+        use Instruction::*;
+
+        // Address 0 runs three times, each time with a different `b`
+        let program = [Add(Reg::B, 1), Set(Reg::C, 3), Jmp(-2)];
+        let mut vm = Vm::borrowed(&program);
+        assert_eq!(vm.loop_detection(), LoopDetection::Address);
+
+        vm.set_loop_detection(LoopDetection::Off);
+        assert_eq!(vm.run_with_budget(9), Outcome::BudgetExhausted);
+        assert_eq!(vm.register(Reg::B), 3);
+
+        let mut vm = Vm::borrowed(&program);
+        vm.patch(1, Jz(Reg::C, 1));
+        assert_eq!(vm.loop_detection(), LoopDetection::State);
+        vm.clear_patches();
+        assert_eq!(vm.loop_detection(), LoopDetection::Address);
+    }
+
+    /// Everything `run` leaves behind, to compare runs by
+    fn run_state(vm: &mut Vm, stepped: bool) -> (Outcome, usize, i32, Vec<usize>, bool) {
+        // Tracing makes `run` go a step at a time
+        if stepped {
+            vm.enable_trace();
+        }
+
+        vm.reset();
+        let outcome = vm.run();
+        vm.take_trace();
+
+        (
+            outcome,
+            vm.pc(),
+            vm.acc(),
+            vm.visited(),
+            vm.fault().is_some(),
+        )
+    }
+
+    #[test]
+    fn test_compiled_run_matches_stepping() {
+        use generator::{GeneratorConfig, ProgramGenerator};
+
+        let config = GeneratorConfig {
+            seed: 48,
+            ..Default::default()
+        };
+
+        let policies = [TerminationPolicy::Exact, TerminationPolicy::PastEnd];
+
+        for program in ProgramGenerator::new(config).take(500) {
+            // Every single flip, and a jump that can land inside a block
+            let len = program.len();
+            let mut patches: Vec<_> = (0..len)
+                .filter_map(|a| Some((a, program[a].flipped()?)))
+                .collect();
+            patches.push((len / 2, Instruction::Jmp((len % 7) as isize - 3)));
+
+            let mut vm = Vm::borrowed(&program);
+
+            for &policy in policies.iter() {
+                vm.set_termination_policy(policy);
+
+                for patch in std::iter::once(None).chain(patches.iter().map(Some)) {
+                    vm.clear_patches();
+                    if let Some(&(address, ins)) = patch {
+                        vm.patch(address, ins);
+                    }
+
+                    let stepped = run_state(&mut vm, true);
+                    assert_eq!(
+                        run_state(&mut vm, false),
+                        stepped,
+                        "{:?} {:?}",
+                        program,
+                        patch
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compiled_run_falls_back() {
+        use Instruction::*;
+
+        // Close enough to overflowing that blocks can't be run whole
+        let mut vm = Vm::new([Acc(i32::MAX - 1), Acc(1), Acc(1), Acc(-5)]);
+        assert_eq!(
+            vm.run(),
+            Outcome::Faulted(Fault::Overflow {
+                address: 2,
+                reg: Reg::ACC
+            })
+        );
+        assert_eq!(vm.visited(), vec![0, 1, 2]);
+
+        // Patches made after the program is compiled are still followed
+        let mut vm = Vm::new(example());
+        assert!(!vm.terminates());
+        vm.reset();
+        vm.patch(7, Nop(-4));
+        assert!(vm.terminates());
+        assert_eq!(vm.acc(), 8);
+
+        // Jumps too far to add up fault rather than panicking
+        let mut vm = Vm::new(vec![Jmp(isize::MAX)]);
+        assert_eq!(
+            vm.run(),
+            Outcome::Faulted(Fault::JumpOutOfRange {
+                address: 0,
+                offset: isize::MAX
+            })
+        );
+        let mut vm = Vm::new(vec![Acc(1), Jmp(isize::MIN)]);
+        vm.set_termination_policy(TerminationPolicy::PastEnd);
+        assert_eq!(
+            vm.run(),
+            Outcome::Faulted(Fault::NegativeJump {
+                address: 1,
+                offset: isize::MIN
+            })
+        );
+
+        // A run that doesn't start fresh goes a step at a time
+        let mut vm = Vm::new(example());
+        vm.step().unwrap();
+        match vm.run() {
+            Outcome::Looped(cycle) => assert_eq!(cycle.prefix, vec![]),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+        assert_eq!(vm.acc(), 5);
+    }
 }
@@ -3,46 +3,30 @@ use day08::*;
 const INPUT: &str = include_str!("../input.txt");
 
 fn main() {
-    part1();
-    part2();
+    let instructions = parse_instructions(INPUT);
+    let mut vm = Vm::borrowed(&instructions);
+
+    part1(&mut vm);
+    part2(&mut vm);
 }
 
-fn part1() {
+fn part1(vm: &mut Vm) {
     println!("*** PART 1 ***");
 
-    let instructions = parse_instructions(INPUT);
-    let mut vm = Vm::new(instructions);
     vm.step_until_loop();
 
     println!("{}", vm.acc());
 }
 
-fn part2() {
+fn part2(vm: &mut Vm) {
     println!("*** PART 2 ***");
 
-    let original_instructions = parse_instructions(INPUT);
-
-    for (i, &ins) in original_instructions.iter().enumerate() {
-        let mut modified_instructions = original_instructions.clone();
+    let address = cfg::find_repair(&vm.instructions()).expect("no single repair found");
+    let flipped = vm.instruction(address).unwrap().flipped().unwrap();
 
-        match ins {
-            Instruction::Acc(_) => {
-                continue;
-            }
+    vm.reset();
+    vm.patch(address, flipped);
+    assert!(vm.terminates());
 
-            Instruction::Nop(x) => {
-                modified_instructions[i] = Instruction::Jmp(x);
-            }
-
-            Instruction::Jmp(x) => {
-                modified_instructions[i] = Instruction::Nop(x);
-            }
-        }
-
-        let mut vm = Vm::new(modified_instructions);
-
-        if vm.terminates() {
-            println!("{}", vm.acc());
-        }
-    }
+    println!("{}", vm.acc());
 }
@@ -0,0 +1,103 @@
+//! A deliberately simple interpreter for the puzzle's instructions, to check
+//! `Vm` against.
+//!
+//! It has none of the `Vm`'s features (patches, tracing, loop detection
+//! modes, budgets), so there's little that can go wrong in it.
+
+use crate::Instruction;
+
+/// How a program run by `run` stopped.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Ending {
+    /// Reached the address just past the last instruction
+    Terminated,
+
+    /// Was about to execute `entry` a second time
+    Looped { entry: usize },
+
+    /// A jump from `address` to before the first instruction
+    NegativeJump { address: usize },
+
+    /// A jump from `address` to beyond the end of the program
+    JumpOutOfRange { address: usize },
+
+    /// The accumulator overflowed at `address`
+    Overflow { address: usize },
+}
+
+/// Run `program` until it stops, returning how and the final accumulator.
+///
+/// # Panics
+///
+/// If the program uses anything but `acc`, `jmp` and `nop`.
+pub fn run(program: &[Instruction]) -> (Ending, i32) {
+    let mut visited = vec![false; program.len()];
+    let mut acc: i32 = 0;
+    let mut pc: usize = 0;
+
+    loop {
+        if pc == program.len() {
+            return (Ending::Terminated, acc);
+        }
+
+        if visited[pc] {
+            return (Ending::Looped { entry: pc }, acc);
+        }
+        visited[pc] = true;
+
+        let offset = match program[pc] {
+            Instruction::Acc(x) => match acc.checked_add(x) {
+                Some(sum) => {
+                    acc = sum;
+                    1
+                }
+                None => return (Ending::Overflow { address: pc }, acc),
+            },
+            Instruction::Jmp(x) => x,
+            Instruction::Nop(_) => 1,
+            ins => panic!("reference interpreter can't run `{}`", ins),
+        };
+
+        let target = pc as isize + offset;
+
+        if target < 0 {
+            return (Ending::NegativeJump { address: pc }, acc);
+        }
+
+        if target as usize > program.len() {
+            return (Ending::JumpOutOfRange { address: pc }, acc);
+        }
+
+        pc = target as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::example;
+    use crate::Instruction::*;
+
+    #[test]
+    fn test_example() {
+        let mut program = example();
+
+        assert_eq!(run(&program), (Ending::Looped { entry: 1 }, 5));
+
+        program[7] = Nop(-4);
+        assert_eq!(run(&program), (Ending::Terminated, 8));
+    }
+
+    #[test]
+    fn test_faults() {
+        assert_eq!(
+            run(&[Acc(1), Jmp(-2)]),
+            (Ending::NegativeJump { address: 1 }, 1)
+        );
+        assert_eq!(run(&[Jmp(2)]), (Ending::JumpOutOfRange { address: 0 }, 0));
+        assert_eq!(
+            run(&[Acc(i32::MIN), Acc(-1)]),
+            (Ending::Overflow { address: 1 }, i32::MIN)
+        );
+    }
+}
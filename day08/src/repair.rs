@@ -0,0 +1,337 @@
+//! Searching for the smallest set of edits that makes a program terminate.
+
+use std::convert::TryFrom;
+
+use crate::cfg::Cfg;
+use crate::{Instruction, Vm, STEP_BUDGET};
+
+/// A kind of edit the repair search is allowed to make.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Mutation {
+    /// Swap a `jmp` for a `nop` or vice versa
+    Flip,
+
+    /// Remove an instruction. Later instructions move up, and no jumps are adjusted.
+    Delete,
+
+    /// Add anything from `-k` to `k` (except zero) to an instruction's argument.
+    /// Nothing is tried if `k` doesn't fit in an `i32`.
+    ChangeArg(u32),
+}
+
+/// One concrete edit, at an address in the original program.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Edit {
+    Flip(usize),
+    Delete(usize),
+    ChangeArg(usize, i32),
+}
+
+impl Edit {
+    pub fn address(&self) -> usize {
+        match *self {
+            Edit::Flip(a) | Edit::Delete(a) | Edit::ChangeArg(a, _) => a,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Repair {
+    /// The edits, in address order
+    pub edits: Vec<Edit>,
+
+    /// The accumulator once the repaired program terminates
+    pub acc: i32,
+}
+
+/// Apply `edits` to `program`, or return `None` if one doesn't make sense
+/// (flipping an `acc`, or an argument overflowing).
+/// Each edit must be at a different address.
+pub fn apply_edits(program: &[Instruction], edits: &[Edit]) -> Option<Vec<Instruction>> {
+    let mut patched = program.to_vec();
+
+    for &edit in edits {
+        match edit {
+            Edit::Flip(a) => patched[a] = patched[a].flipped()?,
+            Edit::ChangeArg(a, delta) => patched[a] = change_arg(patched[a], delta)?,
+            Edit::Delete(_) => {}
+        }
+    }
+
+    // Delete from the back so earlier addresses stay put
+    let mut deletions: Vec<_> = edits
+        .iter()
+        .filter_map(|&e| match e {
+            Edit::Delete(a) => Some(a),
+            _ => None,
+        })
+        .collect();
+    deletions.sort_unstable_by(|a, b| b.cmp(a));
+
+    for a in deletions {
+        patched.remove(a);
+    }
+
+    Some(patched)
+}
+
+/// `ins` with `delta` added to its numeric argument, or `None` for
+/// instructions without one
+fn change_arg(ins: Instruction, delta: i32) -> Option<Instruction> {
+    let offset = |x: isize| x.checked_add(isize::try_from(delta).ok()?);
+
+    match ins {
+        Instruction::Acc(x) => Some(Instruction::Acc(x.checked_add(delta)?)),
+        Instruction::Jmp(x) => Some(Instruction::Jmp(offset(x)?)),
+        Instruction::Nop(x) => Some(Instruction::Nop(offset(x)?)),
+        Instruction::Add(r, x) => Some(Instruction::Add(r, x.checked_add(delta)?)),
+        Instruction::Mul(r, x) => Some(Instruction::Mul(r, x.checked_add(delta)?)),
+        Instruction::Set(r, x) => Some(Instruction::Set(r, x.checked_add(delta)?)),
+        Instruction::Jz(r, x) => Some(Instruction::Jz(r, offset(x)?)),
+        Instruction::Jnz(r, x) => Some(Instruction::Jnz(r, offset(x)?)),
+        Instruction::Out(_) | Instruction::Hlt => None,
+    }
+}
+
+/// Every edit the mutations allow for `ins` at `address`
+fn candidate_edits(ins: Instruction, address: usize, mutations: &[Mutation]) -> Vec<Edit> {
+    let mut edits = vec![];
+
+    for &mutation in mutations {
+        match mutation {
+            Mutation::Flip => {
+                if ins.flipped().is_some() {
+                    edits.push(Edit::Flip(address));
+                }
+            }
+
+            Mutation::Delete => edits.push(Edit::Delete(address)),
+
+            Mutation::ChangeArg(k) => {
+                let k = match i32::try_from(k) {
+                    Ok(k) => k,
+                    Err(_) => continue,
+                };
+
+                for delta in (-k..=k).filter(|&d| d != 0) {
+                    edits.push(Edit::ChangeArg(address, delta));
+                }
+            }
+        }
+    }
+
+    edits
+}
+
+/// Whether the program terminates, and its final accumulator if so
+fn run(program: &[Instruction]) -> Option<i32> {
+    // A loop through a conditional jump might only be found when the
+    // budget runs out, so check statically first, since it's linear.
+    // Otherwise running it is linear too.
+    let conditional = program.iter().any(|ins| ins.is_conditional());
+    if conditional && !Cfg::new(program).terminating()[0] {
+        return None;
+    }
+
+    // Conditional jumps or overflow can still stop it terminating
+    let mut vm = Vm::borrowed(program);
+    if vm.terminates_within(Some(STEP_BUDGET)) {
+        Some(vm.acc())
+    } else {
+        None
+    }
+}
+
+/// Like `run`, but patching `vm`'s program rather than copying it.
+/// Deletions move instructions, so they can't be done this way.
+fn run_patched(vm: &mut Vm<'_>, edits: &[Edit]) -> Option<i32> {
+    vm.clear_patches();
+    vm.reset();
+
+    for &edit in edits {
+        let ins = vm.instruction(edit.address())?;
+
+        let patched = match edit {
+            Edit::Flip(_) => ins.flipped()?,
+            Edit::ChangeArg(_, delta) => change_arg(ins, delta)?,
+            Edit::Delete(_) => unreachable!("deletions need a copy of the program"),
+        };
+
+        vm.patch(edit.address(), patched);
+    }
+
+    if vm.terminates_within(Some(STEP_BUDGET)) {
+        Some(vm.acc())
+    } else {
+        None
+    }
+}
+
+/// Find every smallest set of at most `max_edits` edits (at distinct
+/// addresses) which makes `program` terminate.
+///
+/// If the program already terminates, the only repair is the empty one.
+/// The search tries every combination, so it grows quickly with `max_edits`.
+pub fn repair(program: &[Instruction], mutations: &[Mutation], max_edits: usize) -> Vec<Repair> {
+    if let Some(acc) = run(program) {
+        return vec![Repair { edits: vec![], acc }];
+    }
+
+    // Shared by every candidate that doesn't delete anything
+    let mut vm = Vm::borrowed(program);
+
+    for n in 1..=max_edits.min(program.len()) {
+        let mut repairs = vec![];
+        let mut edits = vec![];
+        search(&mut vm, mutations, n, 0, &mut edits, &mut repairs);
+
+        if !repairs.is_empty() {
+            return repairs;
+        }
+    }
+
+    vec![]
+}
+
+fn search(
+    vm: &mut Vm<'_>,
+    mutations: &[Mutation],
+    remaining: usize,
+    first_address: usize,
+    edits: &mut Vec<Edit>,
+    repairs: &mut Vec<Repair>,
+) {
+    if remaining == 0 {
+        let acc = if edits.iter().any(|e| matches!(e, Edit::Delete(_))) {
+            vm.clear_patches();
+            let program = vm.instructions();
+            apply_edits(&program, edits).and_then(|p| run(&p))
+        } else {
+            run_patched(vm, edits)
+        };
+
+        if let Some(acc) = acc {
+            repairs.push(Repair {
+                edits: edits.clone(),
+                acc,
+            });
+        }
+
+        return;
+    }
+
+    // Leave enough addresses for the rest of the edits
+    for address in first_address..=(vm.len() - remaining) {
+        for edit in candidate_edits(vm.instruction(address).unwrap(), address, mutations) {
+            edits.push(edit);
+            search(vm, mutations, remaining - 1, address + 1, edits, repairs);
+            edits.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::example;
+    use crate::Instruction::*;
+    use crate::Reg;
+
+    #[test]
+    fn test_flip_only() {
+        assert_eq!(
+            repair(&example(), &[Mutation::Flip], 1),
+            vec![Repair {
+                edits: vec![Edit::Flip(7)],
+                acc: 8
+            }]
+        );
+    }
+
+    #[test]
+    fn test_delete() {
+        let repairs = repair(&example(), &[Mutation::Delete], 1);
+
+        assert!(repairs.contains(&Repair {
+            edits: vec![Edit::Delete(7)],
+            acc: 8
+        }));
+        assert!(repairs.iter().all(|r| r.edits.len() == 1));
+    }
+
+    #[test]
+    fn test_change_arg() {
+        // jmp +0 spins forever; jmp +1 falls through to the end
+        let program = [Acc(5), Jmp(0)];
+
+        assert_eq!(
+            repair(&program, &[Mutation::ChangeArg(1)], 1),
+            vec![Repair {
+                edits: vec![Edit::ChangeArg(1, 1)],
+                acc: 5
+            }]
+        );
+    }
+
+    #[test]
+    fn test_needs_two_edits() {
+        let program = [Jmp(0), Acc(2), Jmp(0)];
+
+        assert!(repair(&program, &[Mutation::Flip], 1).is_empty());
+        assert_eq!(
+            repair(&program, &[Mutation::Flip], 2),
+            vec![Repair {
+                edits: vec![Edit::Flip(0), Edit::Flip(2)],
+                acc: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn test_endless_conditional_loop() {
+        // Counts b up for billions of steps before it would overflow
+        let program = [Add(Reg::B, 1), Jnz(Reg::B, -1)];
+
+        assert_eq!(run(&program), None);
+        // Only `add b +0` stops it; `add b +2` runs out of steps instead
+        assert_eq!(
+            repair(&program, &[Mutation::ChangeArg(1)], 1),
+            vec![Repair {
+                edits: vec![Edit::ChangeArg(0, -1)],
+                acc: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_already_terminates() {
+        assert_eq!(
+            repair(&[Acc(3)], &[Mutation::Flip], 1),
+            vec![Repair {
+                edits: vec![],
+                acc: 3
+            }]
+        );
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let program = [Acc(1), Jmp(2), Nop(3), Acc(4)];
+        let edits = [
+            Edit::Delete(0),
+            Edit::Flip(1),
+            Edit::Delete(2),
+            Edit::ChangeArg(3, -4),
+        ];
+
+        assert_eq!(apply_edits(&program, &edits), Some(vec![Nop(2), Acc(0)]));
+        assert_eq!(apply_edits(&program, &[Edit::Flip(0)]), None);
+    }
+
+    #[test]
+    fn test_change_arg_too_big() {
+        let mutations = [Mutation::ChangeArg(1 << 31), Mutation::Flip];
+
+        assert_eq!(candidate_edits(Jmp(0), 3, &mutations), vec![Edit::Flip(3)]);
+    }
+}
@@ -0,0 +1,262 @@
+//! Recording, saving and comparing the path a program takes.
+//!
+//! Traces are saved as JSON lines, one object per executed instruction:
+//!
+//! ```text
+//! {"address":2,"instruction":"jmp +4","acc_before":1,"acc_after":1}
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::asm::assemble_with;
+use crate::{Instruction, InstructionSet, Vm, STEP_BUDGET};
+
+/// One executed instruction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub address: usize,
+    pub instruction: Instruction,
+    pub acc_before: i32,
+    pub acc_after: i32,
+}
+
+/// The first step at which two traces disagree.
+/// An entry is `None` if that trace had already ended.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub step: usize,
+    pub left: Option<TraceEntry>,
+    pub right: Option<TraceEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceError {
+    /// Line of the input the error is on, starting at one
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for TraceError {}
+
+impl TraceEntry {
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"address\":{},\"instruction\":\"{}\",\"acc_before\":{},\"acc_after\":{}}}",
+            self.address, self.instruction, self.acc_before, self.acc_after
+        )
+    }
+
+    /// Parse an object written by `to_json`. Keys may come in any order, but
+    /// this is not a general JSON parser.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let body = json
+            .trim()
+            .strip_prefix('{')
+            .and_then(|s| s.strip_suffix('}'))
+            .ok_or("expected a JSON object")?;
+
+        let mut address = None;
+        let mut instruction = None;
+        let mut acc_before = None;
+        let mut acc_after = None;
+
+        // Values never contain commas or colons, so splitting is enough
+        for field in body.split(',') {
+            let mut parts = field.splitn(2, ':');
+            let key = parts.next().unwrap().trim();
+            let value = parts
+                .next()
+                .ok_or(format!("missing value in `{}`", field))?;
+            let value = value.trim();
+
+            match key {
+                "\"address\"" => address = Some(parse_number(value)?),
+                "\"acc_before\"" => acc_before = Some(parse_number(value)?),
+                "\"acc_after\"" => acc_after = Some(parse_number(value)?),
+                "\"instruction\"" => {
+                    let text = value
+                        .strip_prefix('"')
+                        .and_then(|s| s.strip_suffix('"'))
+                        .ok_or(format!("expected a string, found `{}`", value))?;
+
+                    match assemble_with(text, InstructionSet::LATEST)
+                        .map_err(|e| e.to_string())?
+                        .as_slice()
+                    {
+                        &[ins] => instruction = Some(ins),
+                        _ => return Err(format!("invalid instruction `{}`", text)),
+                    }
+                }
+                _ => return Err(format!("unknown key {}", key)),
+            }
+        }
+
+        Ok(Self {
+            address: address.ok_or("missing address")?,
+            instruction: instruction.ok_or("missing instruction")?,
+            acc_before: acc_before.ok_or("missing acc_before")?,
+            acc_after: acc_after.ok_or("missing acc_after")?,
+        })
+    }
+}
+
+fn parse_number<T: FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number `{}`", value))
+}
+
+pub fn to_json_lines(trace: &[TraceEntry]) -> String {
+    let mut out = String::new();
+
+    for entry in trace {
+        out.push_str(&entry.to_json());
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Read a trace written by `to_json_lines`, skipping blank lines.
+pub fn from_json_lines(json: &str) -> Result<Vec<TraceEntry>, TraceError> {
+    json.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            TraceEntry::from_json(line).map_err(|message| TraceError {
+                line: i + 1,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Run `program` and record its trace, until it terminates or loops, or for
+/// at most `STEP_BUDGET` steps.
+pub fn record(program: &[Instruction]) -> Vec<TraceEntry> {
+    let mut vm = Vm::new(program);
+    vm.enable_trace();
+    vm.run_with_budget(STEP_BUDGET);
+    vm.take_trace().unwrap()
+}
+
+/// Re-run `program` and check that it takes exactly the steps in `trace`.
+pub fn replay(program: &[Instruction], trace: &[TraceEntry]) -> Result<(), Divergence> {
+    match diff(&record(program), trace) {
+        Some(divergence) => Err(divergence),
+        None => Ok(()),
+    }
+}
+
+/// Find the first step where two traces differ, or `None` if they're identical.
+pub fn diff(left: &[TraceEntry], right: &[TraceEntry]) -> Option<Divergence> {
+    let len = left.len().max(right.len());
+
+    (0..len)
+        .map(|step| Divergence {
+            step,
+            left: left.get(step).copied(),
+            right: right.get(step).copied(),
+        })
+        .find(|d| d.left != d.right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::example;
+    use crate::Instruction::*;
+    use crate::Reg;
+
+    #[test]
+    fn test_record() {
+        let trace = record(&example());
+        let addresses: Vec<_> = trace.iter().map(|e| e.address).collect();
+
+        assert_eq!(addresses, vec![0, 1, 2, 6, 7, 3, 4]);
+        assert_eq!(
+            trace[5],
+            TraceEntry {
+                address: 3,
+                instruction: Acc(3),
+                acc_before: 2,
+                acc_after: 5
+            }
+        );
+    }
+
+    #[test]
+    fn test_record_endless_loop() {
+        let trace = record(&[Add(Reg::B, 1), Jnz(Reg::B, -1)]);
+        assert_eq!(trace.len() as u64, STEP_BUDGET);
+    }
+
+    #[test]
+    fn test_trace_only_after_enable() {
+        let mut vm = Vm::new(example());
+        vm.step().unwrap();
+        assert_eq!(vm.trace(), None);
+
+        vm.enable_trace();
+        vm.step().unwrap();
+        assert_eq!(vm.trace().unwrap().len(), 1);
+        assert_eq!(vm.trace().unwrap()[0].address, 1);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let trace = record(&example());
+        let json = to_json_lines(&trace);
+
+        assert_eq!(
+            json.lines().nth(2).unwrap(),
+            "{\"address\":2,\"instruction\":\"jmp +4\",\"acc_before\":1,\"acc_after\":1}"
+        );
+        assert_eq!(from_json_lines(&json).unwrap(), trace);
+    }
+
+    #[test]
+    fn test_json_errors() {
+        let err = from_json_lines("\n{\"address\":1}").unwrap_err();
+
+        assert_eq!(err.line, 2);
+        assert_eq!(err.message, "missing instruction");
+    }
+
+    #[test]
+    fn test_replay() {
+        let trace = record(&example());
+        assert_eq!(replay(&example(), &trace), Ok(()));
+
+        let mut repaired = example();
+        repaired[7] = Nop(-4);
+
+        let divergence = replay(&repaired, &trace).unwrap_err();
+        assert_eq!(divergence.step, 4);
+        assert_eq!(divergence.left.unwrap().instruction, Nop(-4));
+        assert_eq!(divergence.right.unwrap().instruction, Jmp(-4));
+    }
+
+    #[test]
+    fn test_diff_different_lengths() {
+        let trace = record(&example());
+
+        assert_eq!(
+            diff(&trace[..3], &trace),
+            Some(Divergence {
+                step: 3,
+                left: None,
+                right: Some(trace[3])
+            })
+        );
+        assert_eq!(diff(&trace, &trace), None);
+    }
+}
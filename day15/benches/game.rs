@@ -0,0 +1,70 @@
+//! Compares remembering numbers in a `HashMap` with the `Vec` and `HashMap`
+//! split `run_elf_game` uses, at a few different split points, for time and
+//! for how much memory ends up allocated.
+//!
+//! Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+
+use day15::memory::Memory;
+use day15::run_elf_game_hashed;
+
+const INPUT: [u64; 7] = [0, 12, 6, 13, 20, 1, 17];
+
+const FRACTIONS: [usize; 7] = [1, 2, 4, 8, 16, 32, 64];
+
+fn time<T, F: FnOnce() -> T>(f: F) -> (T, Duration) {
+    let start = Instant::now();
+    let res = f();
+    (res, start.elapsed())
+}
+
+/// Play the game the way `run_elf_game_with` does, keeping the `Memory` to
+/// look at afterwards
+fn play(end: usize, dense_len: usize) -> (u64, Memory) {
+    let mut memory = Memory::new(dense_len);
+
+    for (i, &n) in INPUT.iter().enumerate().take(INPUT.len() - 1) {
+        memory.speak(n, i as u32);
+    }
+
+    let mut prev = INPUT[INPUT.len() - 1];
+    for turn in INPUT.len() as u32 - 1..end as u32 - 1 {
+        prev = match memory.speak(prev, turn) {
+            Some(last) => (turn - last) as u64,
+            None => 0,
+        };
+    }
+
+    (prev, memory)
+}
+
+fn main() {
+    for &end in [2020, 300_000, 30_000_000].iter() {
+        println!(
+            "{:<16} {:>12} {:>12} {:>16}",
+            format!("{} turns", end),
+            "time",
+            "MB",
+            "sparse numbers"
+        );
+
+        let (expected, elapsed) = time(|| run_elf_game_hashed(&INPUT, end));
+        println!("{:<16} {:>12?}", "hashed", elapsed);
+
+        for &fraction in FRACTIONS.iter() {
+            let ((res, memory), elapsed) = time(|| play(end, end / fraction));
+            assert_eq!(res, expected);
+
+            println!(
+                "{:<16} {:>12?} {:>12.1} {:>16}",
+                format!("dense 1/{}", fraction),
+                elapsed,
+                memory.heap_bytes() as f64 / 1e6,
+                memory.sparse_len()
+            );
+        }
+
+        println!();
+    }
+}
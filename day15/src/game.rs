@@ -0,0 +1,173 @@
+//! The memory game as a stream of turns.
+
+use crate::memory::Memory;
+use crate::DENSE_FRACTION;
+
+/// How many numbers `ElfGame::new` keeps in a `Vec`, when it doesn't know
+/// how long the game will go on
+const DEFAULT_DENSE_LEN: usize = 1 << 20;
+
+/// Every turn of the game, as `(turn, number spoken)`, with turns counting
+/// from 1.
+///
+/// The game never ends by itself, so limit it with `take`, `nth`,
+/// `take_while` or `find`. It does stop after `u32::MAX` turns, the most
+/// `Memory` can hold.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ElfGame {
+    start: Vec<u64>,
+    memory: Memory,
+
+    /// How many turns have been played
+    played: u32,
+
+    /// The number due to be spoken once the starting numbers run out
+    upcoming: u64,
+}
+
+/// The state of an `ElfGame` after some turn, to go back to with `restore`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    memory: Memory,
+    played: u32,
+    upcoming: u64,
+}
+
+impl ElfGame {
+    pub fn new(start: &[u64]) -> Self {
+        Self::with_dense_len(start, DEFAULT_DENSE_LEN)
+    }
+
+    /// A game that's expected to last about `turns` turns, sized the same way
+    /// as `run_elf_game`. It can still go on for longer.
+    pub fn with_turns(start: &[u64], turns: usize) -> Self {
+        Self::with_dense_len(start, turns / DENSE_FRACTION)
+    }
+
+    fn with_dense_len(start: &[u64], dense_len: usize) -> Self {
+        Self {
+            start: start.to_vec(),
+            memory: Memory::new(dense_len),
+            played: 0,
+            upcoming: 0,
+        }
+    }
+
+    /// How many turns have been played
+    pub fn turn(&self) -> usize {
+        self.played as usize
+    }
+
+    /// The last turn `number` was spoken on, if it has been yet
+    pub fn last_spoken(&self, number: u64) -> Option<usize> {
+        self.memory
+            .last_spoken(number)
+            .map(|turn| turn as usize + 1)
+    }
+
+    /// How many different numbers have been spoken so far
+    pub fn distinct(&self) -> usize {
+        self.memory.distinct()
+    }
+
+    /// Save the state of the game, so it can be rewound to here with `restore`
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            memory: self.memory.clone(),
+            played: self.played,
+            upcoming: self.upcoming,
+        }
+    }
+
+    /// Go back to the turn `checkpoint` was taken after. It must have come
+    /// from a game with the same starting numbers.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) {
+        self.memory.clone_from(&checkpoint.memory);
+        self.played = checkpoint.played;
+        self.upcoming = checkpoint.upcoming;
+    }
+}
+
+impl Iterator for ElfGame {
+    type Item = (usize, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.played == u32::MAX {
+            return None;
+        }
+
+        let number = match self.start.get(self.played as usize) {
+            Some(&n) => n,
+            None => self.upcoming,
+        };
+
+        self.upcoming = match self.memory.speak(number, self.played) {
+            Some(last) => (self.played - last) as u64,
+            None => 0,
+        };
+
+        self.played += 1;
+        Some((self.played as usize, number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_elf_game;
+
+    #[test]
+    fn test_example_turns() {
+        let numbers: Vec<_> = ElfGame::new(&[0, 3, 6]).map(|(_, n)| n).take(10).collect();
+        assert_eq!(numbers, vec![0, 3, 6, 0, 3, 3, 1, 0, 4, 0]);
+
+        let turns: Vec<_> = ElfGame::new(&[0, 3, 6]).map(|(t, _)| t).take(3).collect();
+        assert_eq!(turns, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_matches_run_elf_game() {
+        let start = [0, 12, 6, 13, 20, 1, 17];
+
+        assert_eq!(ElfGame::new(&start).nth(2019), Some((2020, 620)));
+        assert_eq!(
+            ElfGame::with_turns(&start, 100_000).nth(99_999),
+            Some((100_000, run_elf_game(&start, 100_000)))
+        );
+    }
+
+    #[test]
+    fn test_questions() {
+        // The first turn 4 is spoken
+        let mut game = ElfGame::new(&[0, 3, 6]);
+        assert_eq!(game.find(|&(_, n)| n == 4), Some((9, 4)));
+
+        // Turns before the first number above 100, which is 148 on turn 171
+        let small = ElfGame::new(&[0, 3, 6])
+            .take_while(|&(_, n)| n <= 100)
+            .count();
+        assert_eq!(small, 170);
+
+        // How many different numbers by turn 10: 0, 3, 6, 1 and 4
+        let mut game = ElfGame::new(&[0, 3, 6]);
+        game.nth(9);
+        assert_eq!(game.turn(), 10);
+        assert_eq!(game.distinct(), 5);
+        assert_eq!(game.last_spoken(3), Some(6));
+        assert_eq!(game.last_spoken(2), None);
+    }
+
+    #[test]
+    fn test_checkpoint_restore() {
+        let mut game = ElfGame::new(&[1, 3, 2]);
+        game.nth(99);
+
+        let checkpoint = game.checkpoint();
+        let ahead: Vec<_> = game.by_ref().take(50).collect();
+        assert_eq!(game.turn(), 150);
+
+        game.restore(&checkpoint);
+        assert_eq!(game.turn(), 100);
+        assert_eq!(game.by_ref().take(50).collect::<Vec<_>>(), ahead);
+    }
+}
@@ -0,0 +1,148 @@
+pub mod game;
+pub mod memory;
+
+use std::collections::HashMap;
+
+use memory::Memory;
+
+/// Numbers below this fraction of the game's length are remembered in a
+/// `Vec`. For part 2 that allocates about 32MB, the least of the fractions
+/// `benches/game.rs` measures.
+const DENSE_FRACTION: usize = 8;
+
+/// The number spoken on turn `end` (counting from 1) of the game starting
+/// with `start`.
+///
+/// # Panics
+///
+/// If `start` is empty, or `end` doesn't fit in a `u32`.
+pub fn run_elf_game(start: &[u64], end: usize) -> u64 {
+    run_elf_game_with(start, end, end / DENSE_FRACTION)
+}
+
+/// `run_elf_game`, remembering numbers below `dense_len` in a `Vec` and the
+/// rest in a `HashMap`.
+///
+/// # Panics
+///
+/// If `start` is empty, or `end` doesn't fit in a `u32`.
+pub fn run_elf_game_with(start: &[u64], end: usize, dense_len: usize) -> u64 {
+    assert!(
+        end <= u32::MAX as usize,
+        "games are limited to u32::MAX turns"
+    );
+
+    let mut memory = Memory::new(dense_len);
+
+    for (i, &n) in start.iter().enumerate().take(start.len() - 1) {
+        memory.speak(n, i as u32);
+    }
+
+    let mut prev = start[start.len() - 1];
+
+    for i in start.len()..end {
+        prev = match memory.speak(prev, (i - 1) as u32) {
+            Some(idx) => (i - 1 - idx as usize) as u64,
+            None => 0,
+        };
+    }
+
+    prev
+}
+
+/// `run_elf_game` remembering every number in a `HashMap`, as it originally
+/// did. Kept to compare against.
+pub fn run_elf_game_hashed(start: &[u64], end: usize) -> u64 {
+    let mut list = HashMap::new();
+
+    for (i, &n) in start.iter().enumerate().take(start.len() - 1) {
+        list.insert(n, i);
+    }
+
+    let mut prev = start[start.len() - 1];
+
+    for i in start.len()..end {
+        let new = match list.get(&prev) {
+            Some(idx) => (i - 1 - idx) as u64,
+            None => 0,
+        };
+
+        list.insert(prev, i - 1);
+        prev = new;
+    }
+
+    prev
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_elf_game(start: &[u64], end: usize, expected: u64) {
+        assert_eq!(run_elf_game(start, end), expected);
+    }
+
+    #[test]
+    fn test_elf_game_0() {
+        let input = [0, 3, 6];
+        assert_eq!(run_elf_game(&input, 10), 0);
+    }
+
+    #[test]
+    fn test_elf_game_part1() {
+        let inputs = [
+            [1, 3, 2],
+            [2, 1, 3],
+            [1, 2, 3],
+            [2, 3, 1],
+            [3, 2, 1],
+            [3, 1, 2],
+        ];
+
+        let expecteds = [1, 10, 27, 78, 438, 1836];
+
+        for (input, &expected) in inputs.iter().zip(expecteds.iter()) {
+            test_elf_game(input, 2020, expected);
+        }
+    }
+
+    #[test]
+    fn test_elf_game_part2() {
+        let inputs = [
+            [0, 3, 6],
+            [1, 3, 2],
+            [2, 1, 3],
+            [1, 2, 3],
+            [2, 3, 1],
+            [3, 2, 1],
+            [3, 1, 2],
+        ];
+
+        let expecteds = [175594, 2578, 3544142, 261214, 6895259, 18, 362];
+
+        for (input, &expected) in inputs.iter().zip(expecteds.iter()) {
+            test_elf_game(input, 30_000_000, expected);
+        }
+    }
+
+    #[test]
+    fn test_elf_game_big() {
+        let input = [0, 3, 6];
+        let expected = 175594;
+
+        test_elf_game(&input, 30_000_000, expected);
+    }
+
+    #[test]
+    fn test_engines_agree() {
+        let inputs: [&[u64]; 4] = [&[0, 3, 6], &[3, 1, 2], &[7], &[0, 12, 6, 13, 20, 1, 17]];
+
+        for input in inputs.iter() {
+            let expected = run_elf_game_hashed(input, 100_000);
+
+            for &dense_len in [0, 1, 10, 1000, 100_000].iter() {
+                assert_eq!(run_elf_game_with(input, 100_000, dense_len), expected);
+            }
+        }
+    }
+}
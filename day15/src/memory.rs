@@ -0,0 +1,166 @@
+//! Remembering the turn each number was last spoken on.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::mem;
+
+/// Hashes a number by multiplying it by a large odd constant.
+///
+/// `HashMap`'s default hasher resists collision attacks, which the game's
+/// numbers can't mount, and is slower for it.
+#[derive(Default)]
+struct NumberHasher(u64);
+
+impl Hasher for NumberHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, _: &[u8]) {
+        unreachable!("only numbers are hashed");
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = n.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+}
+
+/// The turn each number was last spoken on.
+///
+/// Small numbers are spoken far more often than large ones, so numbers below
+/// a cutoff are kept in a `Vec` indexed by the number, and only the few
+/// larger ones go in a `HashMap`. Numbers and turns are stored as `u32`s, to
+/// halve the size of both. Numbers too big for that can only be starting
+/// numbers, since every number spoken after those is a count of turns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Memory {
+    /// One more than the turn each small number was last spoken on, so that
+    /// zero can mean never
+    dense: Vec<u32>,
+
+    /// The last turn of every number too big for `dense`
+    sparse: HashMap<u32, u32, BuildHasherDefault<NumberHasher>>,
+
+    /// The last turn of every number too big for a `u32`
+    huge: HashMap<u64, u32, BuildHasherDefault<NumberHasher>>,
+
+    /// How many different numbers have been spoken
+    distinct: usize,
+}
+
+impl Memory {
+    /// Memory keeping numbers below `dense_len` in a `Vec`
+    pub fn new(dense_len: usize) -> Self {
+        Self {
+            dense: vec![0; dense_len],
+            sparse: HashMap::default(),
+            huge: HashMap::default(),
+            distinct: 0,
+        }
+    }
+
+    /// Record `number` being spoken on `turn`, returning the turn it was last
+    /// spoken on before that, if ever.
+    ///
+    /// # Panics
+    ///
+    /// If `turn` is `u32::MAX`.
+    pub fn speak(&mut self, number: u64, turn: u32) -> Option<u32> {
+        let last = if number < self.dense.len() as u64 {
+            let slot = &mut self.dense[number as usize];
+            let last = slot.checked_sub(1);
+            *slot = turn.checked_add(1).expect("too many turns");
+            last
+        } else if let Ok(small) = u32::try_from(number) {
+            self.sparse.insert(small, turn)
+        } else {
+            self.huge.insert(number, turn)
+        };
+
+        if last.is_none() {
+            self.distinct += 1;
+        }
+
+        last
+    }
+
+    /// The turn `number` was last spoken on, if ever
+    pub fn last_spoken(&self, number: u64) -> Option<u32> {
+        match self.dense.get(number as usize) {
+            Some(&slot) => slot.checked_sub(1),
+            None => match u32::try_from(number) {
+                Ok(small) => self.sparse.get(&small).copied(),
+                Err(_) => self.huge.get(&number).copied(),
+            },
+        }
+    }
+
+    /// How many different numbers have been spoken
+    pub fn distinct(&self) -> usize {
+        self.distinct
+    }
+
+    /// How many numbers are too big for the `Vec` and have been hashed
+    pub fn sparse_len(&self) -> usize {
+        self.sparse.len() + self.huge.len()
+    }
+
+    /// Roughly how many bytes have been allocated: the `Vec`, and every slot
+    /// in the `HashMap`s with its one byte of bookkeeping
+    pub fn heap_bytes(&self) -> usize {
+        self.dense.capacity() * mem::size_of::<u32>()
+            + self.sparse.capacity() * (mem::size_of::<(u32, u32)>() + 1)
+            + self.huge.capacity() * (mem::size_of::<(u64, u32)>() + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speak() {
+        let mut memory = Memory::new(4);
+
+        assert_eq!(memory.speak(3, 0), None);
+        assert_eq!(memory.speak(10, 1), None);
+        assert_eq!(memory.speak(3, 2), Some(0));
+        assert_eq!(memory.speak(10, 5), Some(1));
+
+        assert_eq!(memory.last_spoken(3), Some(2));
+        assert_eq!(memory.last_spoken(10), Some(5));
+        assert_eq!(memory.last_spoken(0), None);
+        assert_eq!(memory.last_spoken(11), None);
+
+        assert_eq!(memory.distinct(), 2);
+        assert_eq!(memory.sparse_len(), 1);
+        assert!(memory.heap_bytes() >= 4 * 4 + 9);
+    }
+
+    #[test]
+    fn test_huge_numbers() {
+        let mut memory = Memory::new(4);
+        let huge = u32::MAX as u64 + 3;
+
+        assert_eq!(memory.speak(huge, 0), None);
+        assert_eq!(memory.speak(3, 1), None);
+        assert_eq!(memory.speak(huge, 2), Some(0));
+
+        assert_eq!(memory.last_spoken(huge), Some(2));
+        assert_eq!(memory.last_spoken(u64::MAX), None);
+        assert_eq!(memory.sparse_len(), 1);
+    }
+
+    #[test]
+    fn test_turn_zero_is_remembered() {
+        let mut memory = Memory::new(1);
+
+        memory.speak(0, 0);
+        assert_eq!(memory.last_spoken(0), Some(0));
+    }
+}